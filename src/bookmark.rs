@@ -0,0 +1,55 @@
+use std::fs;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+
+// ディレクトリのブックマーク
+// $XDG_STATE_HOME/lsl/bookmarks に "名前<TAB>パス" の形で1行1件保存する
+pub struct Bookmarks {
+    entries: Vec<(String, PathBuf)>,
+}
+
+impl Bookmarks {
+    fn path() -> PathBuf {
+        config::state_dir().join("bookmarks")
+    }
+
+    pub fn load() -> Self {
+        let text = fs::read_to_string(Self::path()).unwrap_or_default();
+        let entries = text
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(name, path)| (name.to_string(), PathBuf::from(path)))
+            .collect();
+        Self { entries }
+    }
+
+    fn save(&self) -> Result<()> {
+        let mut text = String::new();
+        for (name, path) in &self.entries {
+            text.push_str(&format!("{}\t{}\n", name, path.display()));
+        }
+        fs::create_dir_all(config::state_dir())?;
+        fs::write(Self::path(), text)
+    }
+
+    // 同じ名前のものは置き換える
+    pub fn add(&mut self, name: &str, path: &Path) -> Result<()> {
+        match self.entries.iter_mut().find(|(entry, _)| entry == name) {
+            Some(entry) => entry.1 = path.to_path_buf(),
+            None => self.entries.push((name.to_string(), path.to_path_buf())),
+        }
+        self.save()
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<bool> {
+        let len = self.entries.len();
+        self.entries.retain(|(entry, _)| entry != name);
+        if self.entries.len() == len {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+}
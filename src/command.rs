@@ -0,0 +1,336 @@
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use crate::complete;
+
+// コマンドモード (:) で使える組み込みコマンド
+// ! で始まるものはシェルに渡す
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortKey {
+    Name,
+    Size,
+    Time,
+}
+
+pub enum Action {
+    Cd(String),
+    Mkdir(Vec<String>),
+    Touch(Vec<String>),
+    Rm(Vec<String>),
+    Mv(Vec<String>, String),
+    Cp(Vec<String>, String),
+    Chmod(u32, Vec<String>),
+    Sort(SortKey, bool),
+    SetHidden(bool),
+    SetCapture(bool),
+    Bookmark(Option<String>),
+    DeleteBookmark(String),
+    Help,
+    Quit,
+    // (コマンド, 裏で実行するか)
+    Shell(String, bool),
+}
+
+// (名前, 使い方, 説明) -> :help と補完に使う
+pub const COMMANDS: &[(&str, &str, &str)] = &[
+    ("cd", "cd <dir>", "change directory"),
+    (
+        "mkdir",
+        "mkdir <dir>...",
+        "create directories (with parents)",
+    ),
+    (
+        "touch",
+        "touch <file>...",
+        "create files / update timestamps",
+    ),
+    ("rm", "rm <path>...", "move to trash"),
+    ("mv", "mv <src>... <dst>", "move or rename"),
+    ("cp", "cp <src>... <dst>", "copy"),
+    (
+        "chmod",
+        "chmod <mode> <path>...",
+        "change permissions (octal)",
+    ),
+    ("sort", "sort name|size|time [rev]", "change sort order"),
+    (
+        "set",
+        "set hidden|nohidden|capture|nocapture",
+        "show dotfiles / capture :! output in a pane",
+    ),
+    (
+        "bookmark",
+        "bookmark [name] / bookmark -d <name>",
+        "bookmark the current directory / delete a bookmark",
+    ),
+    ("help", "help", "show this help"),
+    ("q", "q", "quit"),
+    (
+        "!",
+        "!<command> [&]",
+        "run with $SHELL, & in background (%f focused, %s selection, %d dir)",
+    ),
+];
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidInput, message)
+}
+
+fn usage(name: &str) -> Error {
+    match COMMANDS.iter().find(|(command, _, _)| *command == name) {
+        Some((_, usage, _)) => invalid(format!("usage: {}", usage)),
+        None => invalid(format!("invalid arguments: {}", name)),
+    }
+}
+
+// 空白で区切る ('...' / "..." / \ でくくったものは1つとして扱う)
+pub fn tokenize(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(invalid("unterminated quote".to_string())),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => word.push(c),
+                            None => return Err(invalid("unterminated quote".to_string())),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(invalid("unterminated quote".to_string())),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                if let Some(c) = chars.next() {
+                    word.push(c);
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+pub fn parse(line: &str) -> Result<Action> {
+    if let Some(command) = line.trim_start().strip_prefix('!') {
+        // 末尾の & -> 裏で実行 (&& は除く)
+        let trimmed = command.trim_end();
+        let background = trimmed.ends_with('&') && !trimmed.ends_with("&&");
+        let command = if background {
+            &trimmed[..trimmed.len() - 1]
+        } else {
+            command
+        };
+        if command.trim().is_empty() {
+            return Err(usage("!"));
+        }
+        return Ok(Action::Shell(command.to_string(), background));
+    }
+
+    let mut words = tokenize(line)?;
+    if words.is_empty() {
+        return Err(invalid("empty command".to_string()));
+    }
+    let name = words.remove(0);
+    let args = words;
+
+    let action = match name.as_str() {
+        "cd" => match args.as_slice() {
+            [dir] => Action::Cd(dir.clone()),
+            [] => Action::Cd("~".to_string()),
+            _ => return Err(usage(&name)),
+        },
+        "mkdir" | "touch" | "rm" if args.is_empty() => return Err(usage(&name)),
+        "mkdir" => Action::Mkdir(args),
+        "touch" => Action::Touch(args),
+        "rm" => Action::Rm(args),
+        "mv" | "cp" => {
+            let mut sources = args;
+            if sources.len() < 2 {
+                return Err(usage(&name));
+            }
+            let destination = sources.pop().unwrap_or_default();
+            if name == "mv" {
+                Action::Mv(sources, destination)
+            } else {
+                Action::Cp(sources, destination)
+            }
+        }
+        "chmod" => match args.split_first() {
+            Some((mode, paths)) if !paths.is_empty() => {
+                Action::Chmod(parse_mode(mode)?, paths.to_vec())
+            }
+            _ => return Err(usage(&name)),
+        },
+        "sort" => {
+            let key = match args.first().map(|arg| arg.as_str()) {
+                Some("name") => SortKey::Name,
+                Some("size") => SortKey::Size,
+                Some("time") => SortKey::Time,
+                _ => return Err(usage(&name)),
+            };
+            match args.get(1).map(|arg| arg.as_str()) {
+                None if args.len() == 1 => Action::Sort(key, false),
+                Some("rev") if args.len() == 2 => Action::Sort(key, true),
+                _ => return Err(usage(&name)),
+            }
+        }
+        "set" => match args.as_slice() {
+            [option] if option == "hidden" => Action::SetHidden(true),
+            [option] if option == "nohidden" => Action::SetHidden(false),
+            [option] if option == "capture" => Action::SetCapture(true),
+            [option] if option == "nocapture" => Action::SetCapture(false),
+            _ => return Err(usage(&name)),
+        },
+        "bookmark" => match args.as_slice() {
+            [] => Action::Bookmark(None),
+            [flag, bookmark] if flag == "-d" => Action::DeleteBookmark(bookmark.clone()),
+            [bookmark] if !bookmark.starts_with('-') => Action::Bookmark(Some(bookmark.clone())),
+            _ => return Err(usage(&name)),
+        },
+        "help" if args.is_empty() => Action::Help,
+        "q" if args.is_empty() => Action::Quit,
+        "help" | "q" => return Err(usage(&name)),
+        _ => {
+            return Err(invalid(format!(
+                "unknown command: {} (use :!{} to run it in the shell)",
+                name, name
+            )))
+        }
+    };
+    Ok(action)
+}
+
+// シェルに渡す文字列の置き換え
+//   %f -> フォーカスしているファイル, %s -> 選択しているもの (空白区切り)
+//   %d -> 今いるディレクトリ, %% -> %
+// それぞれシェルの単語1つになるようクォートする
+pub fn expand_placeholders(
+    command: &str,
+    focused: Option<&str>,
+    selection: &[String],
+    dir: &str,
+) -> Result<String> {
+    let mut expanded = String::new();
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('f') => match focused {
+                Some(focused) => expanded.push_str(&shell_quote(focused)),
+                None => return Err(invalid("%f: nothing is focused".to_string())),
+            },
+            Some('s') => {
+                if selection.is_empty() {
+                    return Err(invalid("%s: nothing is selected".to_string()));
+                }
+                let quoted: Vec<String> = selection.iter().map(|path| shell_quote(path)).collect();
+                expanded.push_str(&quoted.join(" "));
+            }
+            Some('d') => expanded.push_str(&shell_quote(dir)),
+            Some('%') => expanded.push('%'),
+            Some(c) => {
+                expanded.push('%');
+                expanded.push(c);
+            }
+            None => expanded.push('%'),
+        }
+    }
+    Ok(expanded)
+}
+
+// '...' でくくる (中の ' は '\'' にする)
+fn shell_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+// 8進数のパーミッション (例: 644, 0755)
+pub fn parse_mode(text: &str) -> Result<u32> {
+    match u32::from_str_radix(text.trim(), 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => Err(invalid(format!("invalid mode: {}", text))),
+    }
+}
+
+// 補完候補 (入力欄の内容をまるごと置き換えられる形で返す)
+// 最初の単語ならコマンド名、それ以降はパス
+pub fn candidates(input: &str, cwd: &Path) -> Vec<String> {
+    let start = last_word_start(input);
+    let (head, word) = input.split_at(start);
+
+    if head.trim().is_empty() {
+        return COMMANDS
+            .iter()
+            .filter(|(name, _, _)| name.starts_with(word))
+            .map(|(name, _, _)| format!("{}{} ", head, name))
+            .collect();
+    }
+
+    let word = tokenize(word)
+        .ok()
+        .and_then(|words| words.into_iter().next())
+        .unwrap_or_default();
+    complete::candidates(&word, cwd)
+        .into_iter()
+        .map(|candidate| format!("{}{}", head, escape(&candidate)))
+        .collect()
+}
+
+// 最後の単語の始まり (\ で続けた空白は区切りとみなさない)
+fn last_word_start(input: &str) -> usize {
+    let mut start = 0;
+    let mut escaped = false;
+    for (index, c) in input.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == ' ' {
+            start = index + 1;
+        }
+    }
+    start
+}
+
+fn escape(word: &str) -> String {
+    let mut escaped = String::new();
+    for c in word.chars() {
+        if matches!(c, ' ' | '\t' | '\'' | '"' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
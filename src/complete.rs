@@ -0,0 +1,95 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+// 入力中のパスの補完
+
+// 先頭の ~ と $VAR / ${VAR} を展開
+pub fn expand(input: &str) -> String {
+    let input = match input.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{}", env::var("HOME").unwrap_or_default(), rest)
+        }
+        _ => input.to_string(),
+    };
+
+    let mut expanded = String::new();
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            expanded.push(c);
+            continue;
+        }
+        let mut name = String::new();
+        if chars.peek() == Some(&'{') {
+            chars.next();
+            for c in chars.by_ref() {
+                if c == '}' {
+                    break;
+                }
+                name.push(c);
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if !(c.is_ascii_alphanumeric() || c == '_') {
+                    break;
+                }
+                name.push(c);
+                chars.next();
+            }
+        }
+        if name.is_empty() {
+            expanded.push('$');
+        } else {
+            expanded.push_str(&env::var(&name).unwrap_or_default());
+        }
+    }
+    expanded
+}
+
+// inputの続きになり得るもの (ディレクトリには末尾に'/'をつける)
+// 入力したディレクトリ部分はそのまま残し、最後の要素だけを補完する
+pub fn candidates(input: &str, cwd: &Path) -> Vec<String> {
+    let (typed_dir, prefix) = match input.rfind('/') {
+        Some(index) => input.split_at(index + 1),
+        None => ("", input),
+    };
+    let dir = cwd.join(expand(typed_dir));
+    let prefix = expand(prefix);
+
+    let mut candidates: Vec<String> = match fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if !name.starts_with(&prefix) || (name.starts_with('.') && !prefix.starts_with('.'))
+                {
+                    return None;
+                }
+                let is_dir = dir.join(&name).is_dir();
+                Some(format!(
+                    "{}{}{}",
+                    typed_dir,
+                    name,
+                    if is_dir { "/" } else { "" }
+                ))
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    candidates.sort();
+    candidates
+}
+
+pub fn longest_common_prefix(candidates: &[String]) -> String {
+    let mut prefix = match candidates.first() {
+        Some(first) => first.clone(),
+        None => return String::new(),
+    };
+    for candidate in &candidates[1..] {
+        while !candidate.starts_with(&prefix) {
+            prefix.pop();
+        }
+    }
+    prefix
+}
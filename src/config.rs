@@ -0,0 +1,100 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// ~/.config/lsl/config
+//
+//   # コメント
+//   editor = nvim
+//   history_size = 500
+//   capture_output = true
+//   pager = less
+pub struct Config {
+    pub editor: String,
+    pub history_size: usize,
+    pub capture_output: bool,
+    pub pager: String,
+}
+
+impl Config {
+    pub fn load() -> Self {
+        let mut config = Self::default();
+        let text = fs::read_to_string(config_dir().join("config")).unwrap_or_default();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=') {
+                config.set(key.trim(), value.trim());
+            }
+        }
+        config
+    }
+
+    fn set(&mut self, key: &str, value: &str) {
+        match key {
+            "editor" => self.editor = value.to_string(),
+            "history_size" => {
+                if let Ok(size) = value.parse() {
+                    self.history_size = size;
+                }
+            }
+            "capture_output" => self.capture_output = value == "true",
+            "pager" => self.pager = value.to_string(),
+            _ => {}
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            editor: env::var("VISUAL")
+                .or_else(|_| env::var("EDITOR"))
+                .unwrap_or_else(|_| "nvim".to_string()),
+            history_size: 500,
+            capture_output: true,
+            pager: env::var("PAGER").unwrap_or_else(|_| "less".to_string()),
+        }
+    }
+}
+
+fn home_dir() -> PathBuf {
+    PathBuf::from(env::var("HOME").unwrap_or_else(|_| "/".to_string()))
+}
+
+// $XDG_CONFIG_HOME/lsl
+pub fn config_dir() -> PathBuf {
+    match env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir).join("lsl"),
+        _ => home_dir().join(".config").join("lsl"),
+    }
+}
+
+// 新しいファイルの雛形 -> ~/.config/lsl/templates/<拡張子>
+pub fn template_for(path: &Path) -> Option<PathBuf> {
+    let extension = path.extension()?.to_string_lossy().to_string();
+    let template = config_dir().join("templates").join(extension);
+    if template.is_file() {
+        Some(template)
+    } else {
+        None
+    }
+}
+
+// $XDG_DATA_HOME
+pub fn data_home() -> PathBuf {
+    match env::var("XDG_DATA_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home_dir().join(".local").join("share"),
+    }
+}
+
+// $XDG_STATE_HOME/lsl
+pub fn state_dir() -> PathBuf {
+    match env::var("XDG_STATE_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir).join("lsl"),
+        _ => home_dir().join(".local").join("state").join("lsl"),
+    }
+}
@@ -0,0 +1,116 @@
+use std::fs::{self, OpenOptions};
+use std::io::{Error, ErrorKind, Result, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+// ファイル・ディレクトリに対する操作をまとめたもの
+
+// コピー・移動先を決める
+// dstが既存のディレクトリならその中へ、そうでなければdstそのものを新しい名前として扱う
+pub fn resolve_destination(src: &Path, dst: &Path) -> PathBuf {
+    if dst.is_dir() {
+        match src.file_name() {
+            Some(name) => dst.join(name),
+            None => dst.to_path_buf(),
+        }
+    } else {
+        dst.to_path_buf()
+    }
+}
+
+// 既に存在する場合は "name_1.ext", "name_2.ext", ... と空いている名前を探す
+pub fn unique_path(path: &Path) -> PathBuf {
+    if fs::symlink_metadata(path).is_err() {
+        return path.to_path_buf();
+    }
+    let stem = path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let extension = match path.extension() {
+        Some(extension) => format!(".{}", extension.to_string_lossy()),
+        None => String::new(),
+    };
+    let mut counter = 1;
+    loop {
+        let candidate = path.with_file_name(format!("{}_{}{}", stem, counter, extension));
+        if fs::symlink_metadata(&candidate).is_err() {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
+
+// ディレクトリなら中身ごと再帰的にコピー
+pub fn copy_recursive(src: &Path, dst: &Path) -> Result<()> {
+    let metadata = fs::symlink_metadata(src)?;
+    if metadata.is_dir() {
+        fs::create_dir_all(dst)?;
+        for entry in fs::read_dir(src)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &dst.join(entry.file_name()))?;
+        }
+        fs::set_permissions(dst, metadata.permissions())?;
+    } else if metadata.file_type().is_symlink() {
+        std::os::unix::fs::symlink(fs::read_link(src)?, dst)?;
+    } else {
+        fs::copy(src, dst)?;
+    }
+    Ok(())
+}
+
+// renameできない場合（別デバイス間など）はコピーしてから元を消す
+pub fn move_entry(src: &Path, dst: &Path) -> Result<()> {
+    if fs::rename(src, dst).is_ok() {
+        return Ok(());
+    }
+    copy_recursive(src, dst)?;
+    remove_entry(src)
+}
+
+pub fn remove_entry(path: &Path) -> Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+// 新しいファイル・ディレクトリを作る
+// 途中のディレクトリも作り、既にあるものは上書きしない
+// 戻り値は新しく作られた一番上のもの (取り消すときにはこれを消せばよい)
+pub fn create_entry(path: &Path, is_dir: bool, template: Option<&Path>) -> Result<PathBuf> {
+    if fs::symlink_metadata(path).is_ok() {
+        return Err(Error::new(
+            ErrorKind::AlreadyExists,
+            format!("\"{}\" already exists", path.display()),
+        ));
+    }
+
+    let mut topmost = path.to_path_buf();
+    for ancestor in path.ancestors().skip(1) {
+        if ancestor.as_os_str().is_empty() || fs::symlink_metadata(ancestor).is_ok() {
+            break;
+        }
+        topmost = ancestor.to_path_buf();
+    }
+
+    if is_dir {
+        fs::create_dir_all(path)?;
+    } else {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+        if let Some(template) = template {
+            file.write_all(&fs::read(template)?)?;
+            fs::set_permissions(path, fs::metadata(template)?.permissions())?;
+        }
+    }
+    Ok(topmost)
+}
+
+pub fn chmod(path: &Path, mode: u32) -> Result<()> {
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
@@ -0,0 +1,60 @@
+// シェル風のワイルドカード照合
+// * -> 任意の文字列, ? -> 任意の1文字, [abc] / [a-z] / [!abc] -> 文字クラス
+pub fn is_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    match_from(&pattern, &text)
+}
+
+fn match_from(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') => (0..=text.len()).any(|i| match_from(&pattern[1..], &text[i..])),
+        Some('?') => !text.is_empty() && match_from(&pattern[1..], &text[1..]),
+        Some('[') => match (text.first(), class_end(pattern)) {
+            (Some(c), Some(end)) => {
+                class_contains(&pattern[1..end], *c) && match_from(&pattern[end + 1..], &text[1..])
+            }
+            (Some(c), None) => *c == '[' && match_from(&pattern[1..], &text[1..]),
+            (None, _) => false,
+        },
+        Some(p) => text.first() == Some(p) && match_from(&pattern[1..], &text[1..]),
+    }
+}
+
+// '['に対応する']'の位置
+fn class_end(pattern: &[char]) -> Option<usize> {
+    // "[]abc]" や "[!]abc]" の先頭の']'は文字として扱う
+    let mut i = 1;
+    if pattern.get(i) == Some(&'!') {
+        i += 1;
+    }
+    if pattern.get(i) == Some(&']') {
+        i += 1;
+    }
+    pattern[i..].iter().position(|c| *c == ']').map(|p| p + i)
+}
+
+fn class_contains(class: &[char], c: char) -> bool {
+    let (negate, class) = match class.first() {
+        Some('!') => (true, &class[1..]),
+        _ => (false, class),
+    };
+
+    let mut found = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            if class[i] <= c && c <= class[i + 2] {
+                found = true;
+            }
+            i += 3;
+        } else {
+            if class[i] == c {
+                found = true;
+            }
+            i += 1;
+        }
+    }
+    found != negate
+}
@@ -0,0 +1,111 @@
+use std::fs;
+use std::io::Result;
+use std::path::PathBuf;
+
+use crate::config;
+
+// 入力欄ごとの履歴
+// $XDG_STATE_HOME/lsl/history/<name> に1行1件で古い順に保存する
+pub struct History {
+    name: String,
+    entries: Vec<String>,
+    limit: usize,
+    // 上下キーで辿っている位置 (Noneなら入力中の行)
+    position: Option<usize>,
+    draft: String,
+}
+
+impl History {
+    fn path(name: &str) -> PathBuf {
+        config::state_dir().join("history").join(name)
+    }
+
+    pub fn load(name: &str, limit: usize) -> Self {
+        let text = fs::read_to_string(Self::path(name)).unwrap_or_default();
+        let mut history = Self {
+            name: name.to_string(),
+            entries: text.lines().map(|line| line.to_string()).collect(),
+            limit,
+            position: None,
+            draft: String::new(),
+        };
+        history.truncate();
+        history
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = Self::path(&self.name);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut text = String::new();
+        for entry in &self.entries {
+            text.push_str(entry);
+            text.push('\n');
+        }
+        fs::write(path, text)
+    }
+
+    fn truncate(&mut self) {
+        if self.entries.len() > self.limit {
+            let excess = self.entries.len() - self.limit;
+            self.entries.drain(..excess);
+        }
+    }
+
+    // 同じものがあれば古い方を消して一番新しい位置に置く
+    pub fn push(&mut self, entry: &str) {
+        self.reset();
+        let entry = entry.trim();
+        if entry.is_empty() || self.limit == 0 {
+            return;
+        }
+        self.entries.retain(|e| e != entry);
+        self.entries.push(entry.to_string());
+        self.truncate();
+        let _ = self.save();
+    }
+
+    pub fn reset(&mut self) {
+        self.position = None;
+        self.draft.clear();
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(|entry| entry.as_str())
+    }
+
+    // 一つ古いものへ (最初に呼んだときは入力中の行を覚えておく)
+    pub fn previous(&mut self, current: &str) -> Option<String> {
+        let index = match self.position {
+            None if self.entries.is_empty() => return None,
+            None => {
+                self.draft = current.to_string();
+                self.entries.len() - 1
+            }
+            Some(0) => return None,
+            Some(index) => index - 1,
+        };
+        self.position = Some(index);
+        Some(self.entries[index].clone())
+    }
+
+    // 一つ新しいものへ (一番新しいものの次は入力中だった行)
+    pub fn next(&mut self) -> Option<String> {
+        let index = self.position? + 1;
+        if index < self.entries.len() {
+            self.position = Some(index);
+            Some(self.entries[index].clone())
+        } else {
+            self.position = None;
+            Some(std::mem::take(&mut self.draft))
+        }
+    }
+
+    // before より古いものからqueryを含むものを探す
+    pub fn search(&self, query: &str, before: usize) -> Option<usize> {
+        self.entries[..before.min(self.entries.len())]
+            .iter()
+            .rposition(|entry| entry.contains(query))
+    }
+}
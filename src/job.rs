@@ -0,0 +1,187 @@
+use std::io::{Read, Result};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::output;
+
+// 裏で実行するコマンド (:!command &)
+// 出力は後から読めるように溜めておく
+
+// 溜めておく出力の上限 (超えたら古いものから捨てる)
+const MAX_OUTPUT: usize = 4 * 1024 * 1024;
+
+pub enum State {
+    Running,
+    Finished(ExitStatus),
+    Killed,
+}
+
+pub struct Job {
+    pub id: usize,
+    pub command: String,
+    pub cwd: PathBuf,
+    pub state: State,
+    child: Child,
+    output: Arc<Mutex<Vec<u8>>>,
+    started: Instant,
+    finished: Option<Instant>,
+    killed: bool,
+    notified: bool,
+}
+
+impl Job {
+    pub fn elapsed(&self) -> Duration {
+        self.finished.unwrap_or_else(Instant::now) - self.started
+    }
+
+    pub fn is_running(&self) -> bool {
+        matches!(self.state, State::Running)
+    }
+
+    pub fn output(&self) -> String {
+        match self.output.lock() {
+            Ok(output) => String::from_utf8_lossy(&output).to_string(),
+            Err(_) => String::new(),
+        }
+    }
+
+    pub fn status_label(&self) -> String {
+        match &self.state {
+            State::Running if self.killed => "killing".to_string(),
+            State::Running => "running".to_string(),
+            State::Finished(status) => output::status_label(status),
+            State::Killed => "killed".to_string(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Jobs {
+    jobs: Vec<Job>,
+    next_id: usize,
+}
+
+impl Jobs {
+    pub fn spawn(&mut self, shell: &str, command: &str, cwd: &Path) -> Result<usize> {
+        // 標準エラー出力もまとめて受け取る
+        // kill でシェルから起動されたものもまとめて止められるよう別のプロセスグループにする
+        let mut child = Command::new(shell)
+            .arg("-c")
+            .arg(format!("exec 2>&1\n{}", command))
+            .current_dir(cwd)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .process_group(0)
+            .spawn()?;
+
+        let output = Arc::new(Mutex::new(Vec::new()));
+        if let Some(mut stdout) = child.stdout.take() {
+            let output = Arc::clone(&output);
+            thread::spawn(move || {
+                let mut buffer = [0; 8192];
+                while let Ok(n) = stdout.read(&mut buffer) {
+                    if n == 0 {
+                        break;
+                    }
+                    if let Ok(mut output) = output.lock() {
+                        output.extend_from_slice(&buffer[..n]);
+                        if output.len() > MAX_OUTPUT {
+                            let excess = output.len() - MAX_OUTPUT;
+                            output.drain(..excess);
+                        }
+                    }
+                }
+            });
+        }
+
+        self.next_id += 1;
+        self.jobs.push(Job {
+            id: self.next_id,
+            command: command.trim().to_string(),
+            cwd: cwd.to_path_buf(),
+            state: State::Running,
+            child,
+            output,
+            started: Instant::now(),
+            finished: None,
+            killed: false,
+            notified: false,
+        });
+        Ok(self.next_id)
+    }
+
+    pub fn list(&self) -> &[Job] {
+        &self.jobs
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.jobs.iter().any(|job| job.is_running())
+    }
+
+    // 終わったものの状態を更新する -> 新しく終わったものがあればtrue
+    pub fn update(&mut self) -> bool {
+        let mut changed = false;
+        for job in self.jobs.iter_mut().filter(|job| job.is_running()) {
+            if let Ok(Some(status)) = job.child.try_wait() {
+                job.state = if job.killed {
+                    State::Killed
+                } else {
+                    State::Finished(status)
+                };
+                job.finished = Some(Instant::now());
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    // まだ知らせていない終わったもの
+    pub fn take_finished(&mut self) -> Vec<&Job> {
+        let mut finished = Vec::new();
+        for job in self.jobs.iter_mut() {
+            if !job.is_running() && !job.notified {
+                job.notified = true;
+                finished.push(&*job);
+            }
+        }
+        finished
+    }
+
+    pub fn kill(&mut self, id: usize) -> Result<()> {
+        if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+            if job.is_running() {
+                // プロセスグループごと止める (終わったかどうかは update で確かめる)
+                Command::new("kill")
+                    .arg("-TERM")
+                    .arg(format!("-{}", job.child.id()))
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status()?;
+                job.killed = true;
+                job.notified = true;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn kill_all(&mut self) {
+        let running: Vec<usize> = self
+            .jobs
+            .iter()
+            .filter(|job| job.is_running())
+            .map(|job| job.id)
+            .collect();
+        for id in running {
+            let _ = self.kill(id);
+        }
+    }
+
+    // 終わったものを一覧から消す
+    pub fn remove(&mut self, id: usize) {
+        self.jobs.retain(|job| job.id != id || job.is_running());
+    }
+}
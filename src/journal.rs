@@ -0,0 +1,493 @@
+use chrono::Local;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config;
+use crate::fileops;
+use crate::rename;
+use crate::trash;
+
+// 保存しておく操作の数
+const MAX_RECORDS: usize = 100;
+
+// ファイルシステムへの操作の記録
+// 作成・コピーしたものを取り消すときは消さずにゴミ箱へ移し、やり直すときはそこから戻す
+#[derive(Clone, Debug)]
+pub enum Operation {
+    Create {
+        path: PathBuf,
+        modified: Option<SystemTime>,
+        trashed: Option<String>,
+    },
+    Copy {
+        path: PathBuf,
+        modified: Option<SystemTime>,
+        trashed: Option<String>,
+    },
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    Move {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    Trash {
+        original: PathBuf,
+        name: String,
+    },
+    Chmod {
+        path: PathBuf,
+        old_mode: u32,
+        new_mode: u32,
+    },
+}
+
+impl Operation {
+    pub fn create(path: PathBuf) -> Self {
+        Operation::Create {
+            modified: modified_time(&path),
+            path,
+            trashed: None,
+        }
+    }
+
+    pub fn copy(path: PathBuf) -> Self {
+        Operation::Copy {
+            modified: modified_time(&path),
+            path,
+            trashed: None,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Operation::Create { path, .. } => format!("create {}", path.display()),
+            Operation::Copy { path, .. } => format!("copy to {}", path.display()),
+            Operation::Rename { from, to } => format!(
+                "rename {} -> {}",
+                from.display(),
+                to.file_name().unwrap_or_default().to_string_lossy()
+            ),
+            Operation::Move { from, to } => {
+                format!("move {} -> {}", from.display(), to.display())
+            }
+            Operation::Trash { original, .. } => format!("trash {}", original.display()),
+            Operation::Chmod { path, new_mode, .. } => {
+                format!("chmod {:o} {}", new_mode, path.display())
+            }
+        }
+    }
+
+    // 記録したときから状態が変わっていないか (変わっていれば取り消さない)
+    fn check_undo(&self) -> Result<()> {
+        match self {
+            Operation::Create { path, modified, .. } | Operation::Copy { path, modified, .. } => {
+                if modified_time(path).is_none() {
+                    return Err(changed(path, "no longer exists"));
+                }
+                if modified_time(path) != *modified {
+                    return Err(changed(path, "has been modified"));
+                }
+                Ok(())
+            }
+            Operation::Rename { from, to } | Operation::Move { from, to } => {
+                check_movable(to, from)
+            }
+            Operation::Trash { original, name } => {
+                if trash::find(name).is_none() {
+                    return Err(changed(original, "is no longer in the trash"));
+                }
+                check_free(original)
+            }
+            Operation::Chmod { path, new_mode, .. } => check_mode(path, *new_mode),
+        }
+    }
+
+    fn check_redo(&self) -> Result<()> {
+        match self {
+            Operation::Create { path, trashed, .. } | Operation::Copy { path, trashed, .. } => {
+                match trashed.as_deref().and_then(trash::find) {
+                    Some(_) => check_free(path),
+                    None => Err(changed(path, "is no longer in the trash")),
+                }
+            }
+            Operation::Rename { from, to } | Operation::Move { from, to } => {
+                check_movable(from, to)
+            }
+            Operation::Trash { original, .. } => match fs::symlink_metadata(original) {
+                Ok(_) => Ok(()),
+                Err(_) => Err(changed(original, "no longer exists")),
+            },
+            Operation::Chmod { path, old_mode, .. } => check_mode(path, *old_mode),
+        }
+    }
+
+    fn undo(&mut self) -> Result<()> {
+        match self {
+            Operation::Create { path, trashed, .. } | Operation::Copy { path, trashed, .. } => {
+                *trashed = Some(trash::trash(path)?.name);
+            }
+            Operation::Rename { from, to } | Operation::Move { from, to } => {
+                fileops::move_entry(to, from)?;
+            }
+            Operation::Trash { name, .. } => {
+                if let Some(entry) = trash::find(name) {
+                    trash::restore(&entry)?;
+                }
+            }
+            Operation::Chmod { path, old_mode, .. } => fileops::chmod(path, *old_mode)?,
+        }
+        Ok(())
+    }
+
+    fn redo(&mut self) -> Result<()> {
+        match self {
+            Operation::Create {
+                path,
+                trashed,
+                modified,
+            }
+            | Operation::Copy {
+                path,
+                trashed,
+                modified,
+            } => {
+                if let Some(entry) = trashed.as_deref().and_then(trash::find) {
+                    trash::restore(&entry)?;
+                }
+                *trashed = None;
+                *modified = modified_time(path);
+            }
+            Operation::Rename { from, to } | Operation::Move { from, to } => {
+                fileops::move_entry(from, to)?;
+            }
+            Operation::Trash { original, name } => {
+                *name = trash::trash(original)?.name;
+            }
+            Operation::Chmod { path, new_mode, .. } => fileops::chmod(path, *new_mode)?,
+        }
+        Ok(())
+    }
+}
+
+fn changed(path: &Path, reason: &str) -> Error {
+    Error::new(
+        ErrorKind::Other,
+        format!("{} {}, refusing", path.display(), reason),
+    )
+}
+
+fn check_free(path: &Path) -> Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(_) => Err(changed(path, "already exists")),
+        Err(_) => Ok(()),
+    }
+}
+
+fn check_movable(from: &Path, to: &Path) -> Result<()> {
+    if fs::symlink_metadata(from).is_err() {
+        return Err(changed(from, "no longer exists"));
+    }
+    check_free(to)
+}
+
+fn check_mode(path: &Path, mode: u32) -> Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.permissions().mode() & 0o7777 == mode => Ok(()),
+        Ok(_) => Err(changed(path, "has different permissions")),
+        Err(_) => Err(changed(path, "no longer exists")),
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::symlink_metadata(path).ok()?.modified().ok()
+}
+
+// 1回の操作 (まとめて行ったものは1つとして扱う)
+#[derive(Clone, Debug)]
+pub struct Record {
+    pub time: String,
+    pub operations: Vec<Operation>,
+}
+
+impl Record {
+    pub fn describe(&self) -> String {
+        match self.operations.len() {
+            1 => self.operations[0].describe(),
+            n => format!("{} (+{} more)", self.operations[0].describe(), n - 1),
+        }
+    }
+}
+
+// records[..position] が実行済み、records[position..] が取り消したもの (redoできる)
+pub struct Journal {
+    records: Vec<Record>,
+    position: usize,
+}
+
+impl Journal {
+    fn path() -> PathBuf {
+        config::state_dir().join("journal")
+    }
+
+    pub fn load() -> Self {
+        let mut journal = Self {
+            records: Vec::new(),
+            position: 0,
+        };
+        let text = fs::read_to_string(Self::path()).unwrap_or_default();
+        let mut position = None;
+        for line in text.lines() {
+            let fields: Vec<String> = line.split('\t').map(unescape).collect();
+            let field = |i: usize| fields.get(i).cloned().unwrap_or_default();
+            let operation = match fields[0].as_str() {
+                "position" => {
+                    position = field(1).parse().ok();
+                    continue;
+                }
+                "record" => {
+                    journal.records.push(Record {
+                        time: field(1),
+                        operations: Vec::new(),
+                    });
+                    continue;
+                }
+                "create" => Operation::Create {
+                    path: PathBuf::from(field(1)),
+                    modified: parse_time(&field(2)),
+                    trashed: parse_optional(&field(3)),
+                },
+                "copy" => Operation::Copy {
+                    path: PathBuf::from(field(1)),
+                    modified: parse_time(&field(2)),
+                    trashed: parse_optional(&field(3)),
+                },
+                "rename" => Operation::Rename {
+                    from: PathBuf::from(field(1)),
+                    to: PathBuf::from(field(2)),
+                },
+                "move" => Operation::Move {
+                    from: PathBuf::from(field(1)),
+                    to: PathBuf::from(field(2)),
+                },
+                "trash" => Operation::Trash {
+                    original: PathBuf::from(field(1)),
+                    name: field(2),
+                },
+                "chmod" => Operation::Chmod {
+                    path: PathBuf::from(field(1)),
+                    old_mode: u32::from_str_radix(&field(2), 8).unwrap_or(0o644),
+                    new_mode: u32::from_str_radix(&field(3), 8).unwrap_or(0o644),
+                },
+                _ => continue,
+            };
+            if let Some(record) = journal.records.last_mut() {
+                record.operations.push(operation);
+            }
+        }
+        journal
+            .records
+            .retain(|record| !record.operations.is_empty());
+        journal.position = position
+            .unwrap_or(journal.records.len())
+            .min(journal.records.len());
+        journal
+    }
+
+    fn save(&self) -> Result<()> {
+        let mut text = format!("position\t{}\n", self.position);
+        for record in &self.records {
+            text.push_str(&format!("record\t{}\n", escape(&record.time)));
+            for operation in &record.operations {
+                let fields = match operation {
+                    Operation::Create {
+                        path,
+                        modified,
+                        trashed,
+                    } => vec![
+                        "create".to_string(),
+                        path_field(path),
+                        format_time(modified),
+                        format_optional(trashed),
+                    ],
+                    Operation::Copy {
+                        path,
+                        modified,
+                        trashed,
+                    } => vec![
+                        "copy".to_string(),
+                        path_field(path),
+                        format_time(modified),
+                        format_optional(trashed),
+                    ],
+                    Operation::Rename { from, to } => {
+                        vec!["rename".to_string(), path_field(from), path_field(to)]
+                    }
+                    Operation::Move { from, to } => {
+                        vec!["move".to_string(), path_field(from), path_field(to)]
+                    }
+                    Operation::Trash { original, name } => {
+                        vec!["trash".to_string(), path_field(original), escape(name)]
+                    }
+                    Operation::Chmod {
+                        path,
+                        old_mode,
+                        new_mode,
+                    } => vec![
+                        "chmod".to_string(),
+                        path_field(path),
+                        format!("{:o}", old_mode),
+                        format!("{:o}", new_mode),
+                    ],
+                };
+                text.push_str(&fields.join("\t"));
+                text.push('\n');
+            }
+        }
+        fs::create_dir_all(config::state_dir())?;
+        fs::write(Self::path(), text)
+    }
+
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    // 新しく記録するとredoできるものは捨てる
+    pub fn record(&mut self, operations: Vec<Operation>) {
+        if operations.is_empty() {
+            return;
+        }
+        self.records.truncate(self.position);
+        self.records.push(Record {
+            time: Local::now().format("%Y/%m/%d %H:%M").to_string(),
+            operations,
+        });
+        if self.records.len() > MAX_RECORDS {
+            self.records.remove(0);
+        }
+        self.position = self.records.len();
+        let _ = self.save();
+    }
+
+    pub fn undo(&mut self) -> Result<String> {
+        if self.position == 0 {
+            return Err(Error::new(ErrorKind::Other, "nothing to undo"));
+        }
+        let record = &mut self.records[self.position - 1];
+        for operation in record.operations.iter().rev() {
+            operation.check_undo()?;
+        }
+
+        if record
+            .operations
+            .iter()
+            .all(|operation| matches!(operation, Operation::Rename { .. }))
+        {
+            // 一括リネームは a -> b, b -> a のような入れ替えがあり得るのでまとめて戻す
+            let pairs: Vec<(PathBuf, PathBuf)> = record
+                .operations
+                .iter()
+                .filter_map(|operation| match operation {
+                    Operation::Rename { from, to } => Some((to.clone(), from.clone())),
+                    _ => None,
+                })
+                .collect();
+            rename::apply(&pairs)?;
+        } else {
+            for operation in record.operations.iter_mut().rev() {
+                operation.undo()?;
+            }
+        }
+
+        let description = record.describe();
+        self.position -= 1;
+        let _ = self.save();
+        Ok(description)
+    }
+
+    pub fn redo(&mut self) -> Result<String> {
+        if self.position == self.records.len() {
+            return Err(Error::new(ErrorKind::Other, "nothing to redo"));
+        }
+        let record = &mut self.records[self.position];
+        for operation in &record.operations {
+            operation.check_redo()?;
+        }
+
+        if record
+            .operations
+            .iter()
+            .all(|operation| matches!(operation, Operation::Rename { .. }))
+        {
+            let pairs: Vec<(PathBuf, PathBuf)> = record
+                .operations
+                .iter()
+                .filter_map(|operation| match operation {
+                    Operation::Rename { from, to } => Some((from.clone(), to.clone())),
+                    _ => None,
+                })
+                .collect();
+            rename::apply(&pairs)?;
+        } else {
+            for operation in record.operations.iter_mut() {
+                operation.redo()?;
+            }
+        }
+
+        let description = record.describe();
+        self.position += 1;
+        let _ = self.save();
+        Ok(description)
+    }
+}
+
+// タブと改行を含むパスでも1行に収まるように
+fn escape(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\t', "%09")
+        .replace('\n', "%0A")
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("%0A", "\n")
+        .replace("%09", "\t")
+        .replace("%25", "%")
+}
+
+fn path_field(path: &Path) -> String {
+    escape(&path.display().to_string())
+}
+
+fn format_optional(value: &Option<String>) -> String {
+    match value {
+        Some(value) => escape(value),
+        None => "-".to_string(),
+    }
+}
+
+fn parse_optional(text: &str) -> Option<String> {
+    match text {
+        "-" | "" => None,
+        _ => Some(text.to_string()),
+    }
+}
+
+fn format_time(time: &Option<SystemTime>) -> String {
+    match time.and_then(|time| time.duration_since(UNIX_EPOCH).ok()) {
+        Some(duration) => format!("{}.{:09}", duration.as_secs(), duration.subsec_nanos()),
+        None => "-".to_string(),
+    }
+}
+
+fn parse_time(text: &str) -> Option<SystemTime> {
+    let (secs, nanos) = text.split_once('.')?;
+    Some(UNIX_EPOCH + Duration::new(secs.parse().ok()?, nanos.parse().ok()?))
+}
@@ -0,0 +1,150 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+// 入力欄の1行エディタ
+// cursorはbufferのバイト位置 (常に文字の境界にある)
+#[derive(Clone, Debug, Default)]
+pub struct LineEditor {
+    buffer: String,
+    cursor: usize,
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.buffer
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+        self.cursor = 0;
+    }
+
+    // カーソルは末尾に
+    pub fn set(&mut self, text: String) {
+        self.cursor = text.len();
+        self.buffer = text;
+    }
+
+    pub fn set_with_cursor(&mut self, text: String, cursor: usize) {
+        self.buffer = text;
+        self.cursor = cursor.min(self.buffer.len());
+        while !self.buffer.is_char_boundary(self.cursor) {
+            self.cursor -= 1;
+        }
+    }
+
+    // (カーソルより前, カーソルより後)
+    pub fn split_at_cursor(&self) -> (&str, &str) {
+        self.buffer.split_at(self.cursor)
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.buffer.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    // 貼り付け -> 1行の入力なので改行は空白にする
+    pub fn insert_str(&mut self, text: &str) {
+        let text = text
+            .trim_end_matches(['\r', '\n'])
+            .replace(['\r', '\n'], " ");
+        self.buffer.insert_str(self.cursor, &text);
+        self.cursor += text.len();
+    }
+
+    // 編集のためのキーなら処理してtrueを返す
+    // Enter / Esc / Tab / 上下 などはそれぞれの入力欄で扱う
+    pub fn handle_key(&mut self, key: &KeyEvent) -> bool {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        match key.code {
+            KeyCode::Char('a') if control => self.cursor = 0,
+            KeyCode::Char('e') if control => self.cursor = self.buffer.len(),
+            KeyCode::Char('b') if control => self.move_left(),
+            KeyCode::Char('f') if control => self.move_right(),
+            KeyCode::Char('b') if alt => self.cursor = self.previous_word(),
+            KeyCode::Char('f') if alt => self.cursor = self.next_word(),
+            KeyCode::Char('d') if alt => {
+                let end = self.next_word();
+                self.buffer.replace_range(self.cursor..end, "");
+            }
+            KeyCode::Char('w') if control => {
+                let start = self.previous_word();
+                self.buffer.replace_range(start..self.cursor, "");
+                self.cursor = start;
+            }
+            KeyCode::Char('u') if control => {
+                self.buffer.replace_range(..self.cursor, "");
+                self.cursor = 0;
+            }
+            KeyCode::Char('k') if control => self.buffer.truncate(self.cursor),
+            KeyCode::Char(c) if !control && !alt => self.insert(c),
+            KeyCode::Backspace if alt => {
+                let start = self.previous_word();
+                self.buffer.replace_range(start..self.cursor, "");
+                self.cursor = start;
+            }
+            KeyCode::Backspace => {
+                if let Some(c) = self.buffer[..self.cursor].chars().next_back() {
+                    self.cursor -= c.len_utf8();
+                    self.buffer.remove(self.cursor);
+                }
+            }
+            KeyCode::Delete => {
+                if self.cursor < self.buffer.len() {
+                    self.buffer.remove(self.cursor);
+                }
+            }
+            KeyCode::Left if control || alt => self.cursor = self.previous_word(),
+            KeyCode::Right if control || alt => self.cursor = self.next_word(),
+            KeyCode::Left => self.move_left(),
+            KeyCode::Right => self.move_right(),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.buffer.len(),
+            _ => return false,
+        }
+        true
+    }
+
+    fn move_left(&mut self) {
+        if let Some(c) = self.buffer[..self.cursor].chars().next_back() {
+            self.cursor -= c.len_utf8();
+        }
+    }
+
+    fn move_right(&mut self) {
+        if let Some(c) = self.buffer[self.cursor..].chars().next() {
+            self.cursor += c.len_utf8();
+        }
+    }
+
+    // 単語の区切り -> 空白と'/'
+    fn is_separator(c: char) -> bool {
+        c.is_whitespace() || c == '/'
+    }
+
+    fn previous_word(&self) -> usize {
+        let before = &self.buffer[..self.cursor];
+        let trimmed = before.trim_end_matches(Self::is_separator);
+        match trimmed.rfind(Self::is_separator) {
+            Some(index) => index + 1,
+            None => 0,
+        }
+    }
+
+    fn next_word(&self) -> usize {
+        let after = &self.buffer[self.cursor..];
+        let skipped = after.len() - after.trim_start_matches(Self::is_separator).len();
+        match after[skipped..].find(Self::is_separator) {
+            Some(index) => self.cursor + skipped + index,
+            None => self.buffer.len(),
+        }
+    }
+}
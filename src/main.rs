@@ -2,32 +2,60 @@ use chrono::prelude::{DateTime, Utc};
 use crossterm::queue;
 use crossterm::{
     cursor::{Hide, MoveTo, MoveToColumn, MoveToRow, Show},
-    event::{read, Event, KeyCode, KeyEvent},
+    event::{
+        poll, read, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEvent,
+        KeyModifiers,
+    },
     execute,
-    style::{Color, Print, SetBackgroundColor, SetForegroundColor},
+    style::{Attribute, Color, Print, SetAttribute, SetBackgroundColor, SetForegroundColor},
     terminal::{
         disable_raw_mode, enable_raw_mode, size, Clear, ClearType, EnterAlternateScreen,
         LeaveAlternateScreen,
     },
 };
 use std::fs;
-use std::io::Result;
 use std::io::Write;
-use std::path::Path;
-use std::process::Command;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant, SystemTime};
 extern crate chrono;
 use std::{env, os::unix::fs::PermissionsExt};
 
-use std::fs::File;
-
+mod bookmark;
+mod command;
+mod complete;
+mod config;
+mod fileops;
+mod glob;
+mod history;
+mod job;
+mod journal;
+mod lineedit;
+mod output;
+mod rename;
+mod transfer;
+mod trash;
 mod window;
 
 use window::textbox::Style;
 use window::textbox::TextBox;
+use window::textbox::{char_width, display_width, truncate_to_width};
 use window::textline::TextLine;
 use window::window::Mode;
 use window::window::Window;
 
+use bookmark::Bookmarks;
+use command::{Action, SortKey};
+use config::Config;
+use history::History;
+use job::Jobs;
+use journal::{Journal, Operation};
+use lineedit::LineEditor;
+use output::Output;
+use transfer::{Resolution, Transfer};
+use trash::TrashEntry;
+
 const GRUVBOX_BACKGROUND: Color = Color::Rgb {
     r: 40,
     g: 40,
@@ -74,7 +102,31 @@ struct App {
     focus_page: usize,
     pwd: String,
     mode: Mode,
-    input_buffer: String,
+    input_buffer: LineEditor,
+    marked_files: Vec<String>,
+    mark_anchor: Option<usize>,
+    message: String,
+    clipboard: Vec<String>,
+    clipboard_cut: bool,
+    transfer: Option<Transfer>,
+    config: Config,
+    rename_plan: Vec<(PathBuf, PathBuf)>,
+    trash_entries: Vec<TrashEntry>,
+    trash_focus: usize,
+    journal: Journal,
+    history_focus: usize,
+    completion: Vec<String>,
+    completion_index: Option<usize>,
+    command_history: History,
+    cd_history: History,
+    history_search: Option<(String, Option<usize>)>,
+    sort_key: SortKey,
+    sort_reverse: bool,
+    show_hidden: bool,
+    bookmarks: Bookmarks,
+    output: Option<Output>,
+    jobs: Jobs,
+    jobs_focus: usize,
 }
 
 impl App {
@@ -89,6 +141,9 @@ impl App {
         start_w: u16,
         start_h: u16,
     ) -> Self {
+        let config = Config::load();
+        let command_history = History::load("command", config.history_size);
+        let cd_history = History::load("cd", config.history_size);
         Self {
             mostbig_size_filename,
             mostbig_size_length,
@@ -104,7 +159,31 @@ impl App {
             focus_page: 0,
             pwd: String::new(),
             mode: Mode::Nomal,
-            input_buffer: String::new(),
+            input_buffer: LineEditor::new(),
+            marked_files: Vec::new(),
+            mark_anchor: None,
+            message: String::new(),
+            clipboard: Vec::new(),
+            clipboard_cut: false,
+            transfer: None,
+            config,
+            rename_plan: Vec::new(),
+            trash_entries: Vec::new(),
+            trash_focus: 0,
+            journal: Journal::load(),
+            history_focus: 0,
+            completion: Vec::new(),
+            completion_index: None,
+            command_history,
+            cd_history,
+            history_search: None,
+            sort_key: SortKey::Name,
+            sort_reverse: false,
+            show_hidden: true,
+            bookmarks: Bookmarks::load(),
+            output: None,
+            jobs: Jobs::default(),
+            jobs_focus: 0,
         }
     }
 
@@ -116,13 +195,18 @@ impl App {
         match fs::read_dir("./") {
             Ok(entries) => {
                 self.pwd = env::current_dir().unwrap().display().to_string();
-                for entry in entries {
+                let mut filenames: Vec<String> = entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.file_name().to_string_lossy().to_string())
+                    .filter(|filename| self.show_hidden || !filename.starts_with('.'))
+                    .collect();
+                self.sort_filenames(&mut filenames);
+                for filename in filenames {
                     if page_counter > self.window_height as usize - 3 {
                         self.in_dir_files.push(tmp_vec.clone());
                         tmp_vec.clear();
                         page_counter = 0;
                     }
-                    let filename = entry.unwrap().file_name().to_string_lossy().to_string();
                     let filesize = std::fs::metadata(filename.clone())
                         .unwrap()
                         .len()
@@ -148,6 +232,22 @@ impl App {
         };
     }
 
+    // :sort で選んだ順に並べる (size / time は大きいもの・新しいものから)
+    fn sort_filenames(&self, filenames: &mut [String]) {
+        match self.sort_key {
+            SortKey::Name => filenames.sort(),
+            SortKey::Size => filenames.sort_by_cached_key(|filename| {
+                std::cmp::Reverse(fs::metadata(filename).map(|m| m.len()).unwrap_or(0))
+            }),
+            SortKey::Time => filenames.sort_by_cached_key(|filename| {
+                std::cmp::Reverse(fs::metadata(filename).and_then(|m| m.modified()).ok())
+            }),
+        }
+        if self.sort_reverse {
+            filenames.reverse();
+        }
+    }
+
     fn render_dir_view(&mut self) -> Result<()> {
         queue!(
             std::io::stderr(),
@@ -161,29 +261,28 @@ impl App {
             )?;
         }
         self.focus_index = 0;
+        self.mark_anchor = None;
         self.get_in_dir().unwrap();
+        if self.focus_page >= self.in_dir_files.len() {
+            self.focus_page = self.in_dir_files.len().saturating_sub(1);
+        }
         Ok(())
     }
 
     fn cd(&mut self, path: String) -> Result<()> {
-        let mv_to = format!("{}/{}", env::current_dir().unwrap().to_str().unwrap(), path);
-        match env::set_current_dir(Path::new(&mv_to)) {
-            Ok(_) => {
-                // pathに指されているものがディレクトリである
-                // in_dir_dataをpathの内容に上書き
+        let mv_to = Path::new(&self.pwd).join(complete::expand(&path));
+        env::set_current_dir(&mv_to)?;
+        // pathに指されているものがディレクトリである
+        // in_dir_dataをpathの内容に上書き
 
-                self.focus_page = 0;
-                self.render_dir_view()
-            }
-            Err(e) => {
-                self.input_buffer.clear();
-                Err(e)
-            }
-        }
+        self.focus_page = 0;
+        self.render_dir_view()
     }
 
     fn nomal_key_read(&mut self, max_down: usize) -> Result<()> {
-        match read()? {
+        let event = read()?;
+        self.message.clear();
+        match event {
             // ESC ----------------------------------------------------------------------------
             Event::Key(KeyEvent {
                 code: KeyCode::Esc, ..
@@ -209,8 +308,35 @@ impl App {
                         ..
                     }) => {
                         //commandmode
+                        self.cd_history.reset();
                         self.mode = Mode::Cd;
                     }
+                    Event::Key(KeyEvent {
+                        code: KeyCode::Char('c'),
+                        ..
+                    }) => {
+                        self.mode = Mode::Copy;
+                    }
+                    Event::Key(KeyEvent {
+                        code: KeyCode::Char('m'),
+                        ..
+                    }) => {
+                        self.mode = Mode::Move;
+                    }
+                    Event::Key(KeyEvent {
+                        code: KeyCode::Char('p'),
+                        ..
+                    }) => {
+                        self.mode = Mode::Chmod;
+                    }
+                    Event::Key(KeyEvent {
+                        code: KeyCode::Char('u'),
+                        ..
+                    }) => {
+                        // 操作の履歴
+                        self.history_focus = 0;
+                        self.mode = Mode::History;
+                    }
                     _ => {
                         return Ok(());
                     }
@@ -225,19 +351,14 @@ impl App {
                 code: KeyCode::Enter,
                 ..
             }) => {
-                match self.cd(self.in_dir_files[self.focus_page][self.focus_index].clone()) {
-                    Err(e) => {
-                        // pathに指されているものがファイルである
-                        // enter keyを押した時にファイルであればvimを起動
-                        queue!(std::io::stderr(), Show, LeaveAlternateScreen)?;
-
-                        let mut child = Command::new("nvim")
-                            .arg(self.in_dir_files[self.focus_page][self.focus_index].clone())
-                            .spawn()?;
-                        child.wait().unwrap();
-                        queue!(std::io::stderr(), Hide, EnterAlternateScreen)?;
-                    }
-                    _ => {}
+                let name = match self.focused_name() {
+                    Some(name) => name,
+                    None => return Ok(()),
+                };
+                if self.cd(name.clone()).is_err() {
+                    // pathに指されているものがファイルである
+                    // enter keyを押した時にファイルであればエディタを起動
+                    self.open_in_editor(vec![name])?;
                 }
                 let _ = std::io::stdout().flush();
             }
@@ -274,6 +395,13 @@ impl App {
                 self.mode = Mode::Addfile;
             }
 
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('N'),
+                ..
+            }) => {
+                self.mode = Mode::Adddir;
+            }
+
             Event::Key(KeyEvent {
                 code: KeyCode::Char('d'),
                 ..
@@ -281,35 +409,351 @@ impl App {
                 self.mode = Mode::Delfile;
             }
 
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('D'),
+                ..
+            }) => {
+                self.mode = Mode::Purge;
+            }
+
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('T'),
+                ..
+            }) => {
+                self.trash_entries = trash::list();
+                self.trash_focus = 0;
+                self.mode = Mode::Trash;
+            }
+
+            // ジョブ ---------------------------------------------------------------------------
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('&'),
+                ..
+            }) => {
+                // 裏で実行するコマンドの入力 ("!" と " &" の間にカーソルを置く)
+                self.command_history.reset();
+                self.input_buffer.set_with_cursor("! &".to_string(), 1);
+                self.mode = Mode::Command;
+            }
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('J'),
+                ..
+            }) => {
+                self.jobs_focus = 0;
+                self.mode = Mode::Jobs;
+            }
+            // ジョブ ---------------------------------------------------------------------------
             Event::Key(KeyEvent {
                 code: KeyCode::Char(':'),
                 ..
             }) => {
+                self.command_history.reset();
                 self.mode = Mode::Command;
             }
 
+            // 選択 ---------------------------------------------------------------------------
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('v'),
+                ..
+            }) => {
+                self.toggle_mark();
+            }
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('V'),
+                ..
+            }) => {
+                self.range_mark();
+            }
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('A'),
+                ..
+            }) => {
+                self.mark_all();
+            }
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('I'),
+                ..
+            }) => {
+                self.invert_marks();
+            }
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('*'),
+                ..
+            }) => {
+                self.mode = Mode::Glob;
+            }
+            // 選択 ---------------------------------------------------------------------------
+
+            // yank / cut / paste -------------------------------------------------------------
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('y'),
+                ..
+            }) => {
+                self.yank(false);
+            }
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('x'),
+                ..
+            }) => {
+                self.yank(true);
+            }
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('p'),
+                ..
+            }) => {
+                let destination = PathBuf::from(&self.pwd);
+                if let Err(e) =
+                    self.start_transfer(self.clipboard.clone(), destination, self.clipboard_cut)
+                {
+                    self.message = e.to_string();
+                }
+            }
+            // yank / cut / paste -------------------------------------------------------------
+
+            // undo / redo -------------------------------------------------------------------
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('u'),
+                ..
+            }) => {
+                self.undo()?;
+            }
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('r'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            }) => {
+                self.redo()?;
+            }
+            // undo / redo -------------------------------------------------------------------
+
+            // rename -------------------------------------------------------------------------
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('r'),
+                ..
+            }) => {
+                if let Some(name) = self.focused_name() {
+                    // 拡張子の手前にカーソルを置く
+                    let cursor = match name.rfind('.') {
+                        Some(index) if index > 0 => index,
+                        _ => name.len(),
+                    };
+                    self.input_buffer.set_with_cursor(name, cursor);
+                    self.mode = Mode::Rename;
+                }
+            }
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('R'),
+                ..
+            }) => {
+                if let Err(e) = self.bulk_rename() {
+                    self.message = e.to_string();
+                }
+            }
+            // rename -------------------------------------------------------------------------
             _ => {} // WASD ---------------------------------------------------------------------------
         }
         self.cursor.0 = 1;
         return Ok(());
     }
 
+    // Tab -> 補完 (候補が1つなら確定、複数なら共通部分まで埋め、もう一度押すと順に選ぶ)
+    // 候補を出している間は上下キーでも選べる
+    fn complete_key(&mut self, code: KeyCode) {
+        if !matches!(
+            code,
+            KeyCode::Tab | KeyCode::BackTab | KeyCode::Up | KeyCode::Down
+        ) {
+            self.completion.clear();
+            self.completion_index = None;
+            return;
+        }
+
+        if self.completion.is_empty() {
+            if !matches!(code, KeyCode::Tab) {
+                return;
+            }
+            let candidates = self.completion_candidates();
+            match candidates.len() {
+                0 => {}
+                1 => self.input_buffer.set(candidates[0].clone()),
+                _ => {
+                    let common = complete::longest_common_prefix(&candidates);
+                    if common.len() > self.input_buffer.as_str().len() {
+                        self.input_buffer.set(common);
+                    }
+                    self.completion = candidates;
+                }
+            }
+            return;
+        }
+
+        let len = self.completion.len();
+        let index = match (code, self.completion_index) {
+            (KeyCode::Tab | KeyCode::Down, Some(index)) => (index + 1) % len,
+            (KeyCode::BackTab | KeyCode::Up, Some(index)) => (index + len - 1) % len,
+            (KeyCode::BackTab | KeyCode::Up, None) => len - 1,
+            _ => 0,
+        };
+        self.completion_index = Some(index);
+        self.input_buffer.set(self.completion[index].clone());
+    }
+
+    fn completion_candidates(&self) -> Vec<String> {
+        match self.mode {
+            Mode::Command => command::candidates(self.input_buffer.as_str(), Path::new(&self.pwd)),
+            _ => complete::candidates(self.input_buffer.as_str(), Path::new(&self.pwd)),
+        }
+    }
+
+    // 補完候補の一覧 (選んでいるもの)
+    fn completion_list(&self) -> (Vec<String>, Option<usize>) {
+        if self.completion.is_empty() {
+            (self.completion_candidates(), None)
+        } else {
+            (self.completion.clone(), self.completion_index)
+        }
+    }
+
+    // 入力欄のキーを読む
+    // 編集 (カーソル移動・削除・貼り付けなど) はここで済ませ、それ以外のキーを返す
+    fn read_prompt_key(&mut self) -> Result<Option<KeyCode>> {
+        let event = read()?;
+        if self.history_search.is_some() && !self.history_search_key(&event) {
+            return Ok(None);
+        }
+        match event {
+            Event::Paste(text) => {
+                self.input_buffer.insert_str(&text);
+                self.complete_key(KeyCode::Null);
+                Ok(None)
+            }
+            Event::Key(key) => {
+                if self.input_buffer.handle_key(&key) {
+                    self.complete_key(KeyCode::Null);
+                    Ok(None)
+                } else if self.completion.is_empty() && self.history_key(&key) {
+                    Ok(None)
+                } else {
+                    Ok(Some(key.code))
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+
+    // 入力の履歴 ---------------------------------------------------------------------------
+
+    fn history(&self) -> Option<&History> {
+        match self.mode {
+            Mode::Command => Some(&self.command_history),
+            Mode::Cd => Some(&self.cd_history),
+            _ => None,
+        }
+    }
+
+    fn history_mut(&mut self) -> Option<&mut History> {
+        match self.mode {
+            Mode::Command => Some(&mut self.command_history),
+            Mode::Cd => Some(&mut self.cd_history),
+            _ => None,
+        }
+    }
+
+    // 上下 -> 履歴を辿る, Ctrl-r -> 履歴を検索
+    fn history_key(&mut self, key: &KeyEvent) -> bool {
+        let current = self.input_buffer.as_str().to_string();
+        let history = match self.history_mut() {
+            Some(history) => history,
+            None => return false,
+        };
+        let entry = match key.code {
+            KeyCode::Up => history.previous(&current),
+            KeyCode::Down => history.next(),
+            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.history_search = Some((String::new(), None));
+                return true;
+            }
+            _ => return false,
+        };
+        if let Some(entry) = entry {
+            self.input_buffer.set(entry);
+        }
+        true
+    }
+
+    // 検索中のキー
+    // 検索を終えてキーをそのまま入力欄で扱う場合はtrue (見つけたものは入力欄に入れる)
+    fn history_search_key(&mut self, event: &Event) -> bool {
+        let (mut query, found) = match self.history_search.take() {
+            Some(search) => search,
+            None => return true,
+        };
+        let control = |modifiers: &KeyModifiers| modifiers.contains(KeyModifiers::CONTROL);
+        // 今見つけているものから探し直す (Ctrl-r はそれより古いものから)
+        let before = match event {
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('r'),
+                modifiers,
+                ..
+            }) if control(modifiers) => found.unwrap_or(usize::MAX),
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('g'),
+                modifiers,
+                ..
+            }) if control(modifiers) => return false,
+            Event::Key(KeyEvent {
+                code: KeyCode::Esc, ..
+            }) => return false,
+            Event::Key(KeyEvent {
+                code: KeyCode::Backspace,
+                ..
+            }) => {
+                query.pop();
+                usize::MAX
+            }
+            Event::Key(KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers,
+                ..
+            }) if !control(modifiers) => {
+                query.push(*c);
+                found.map_or(usize::MAX, |index| index + 1)
+            }
+            Event::Paste(text) => {
+                query.push_str(text);
+                found.map_or(usize::MAX, |index| index + 1)
+            }
+            _ => {
+                if let Some(entry) =
+                    found.and_then(|index| self.history().and_then(|history| history.get(index)))
+                {
+                    self.input_buffer.set(entry.to_string());
+                }
+                return true;
+            }
+        };
+        let found = self
+            .history()
+            .and_then(|history| history.search(&query, before))
+            .or(found);
+        self.history_search = Some((query, found));
+        false
+    }
+    // 入力の履歴 ---------------------------------------------------------------------------
+
     fn change_directory(&mut self) -> Result<()> {
-        if let Event::Key(KeyEvent { code, .. }) = read()? {
+        if let Some(code) = self.read_prompt_key()? {
+            self.complete_key(code);
             match code {
                 KeyCode::Esc => {
                     self.input_buffer.clear();
                     self.mode = Mode::Nomal;
                 }
-                KeyCode::Char(c) => {
-                    self.input_buffer.push(c);
-                }
-                KeyCode::Backspace => {
-                    self.input_buffer.pop();
-                }
                 KeyCode::Enter => {
-                    self.cd(self.input_buffer.clone())?;
+                    self.cd_history.push(self.input_buffer.as_str());
+                    let result = self.cd(self.input_buffer.as_str().to_string());
                     self.input_buffer.clear();
+                    result?;
                     let _ = std::io::stdout().flush();
                     self.mode = Mode::Nomal;
                 }
@@ -322,36 +766,45 @@ impl App {
     }
 
     fn add_new_file_or_directory(&mut self) -> Result<()> {
-        if let Event::Key(KeyEvent { code, .. }) = read()? {
+        if let Some(code) = self.read_prompt_key()? {
+            self.complete_key(code);
             match code {
                 KeyCode::Esc => {
                     self.input_buffer.clear();
                     self.mode = Mode::Nomal;
                 }
-                KeyCode::Char(c) => {
-                    self.input_buffer.push(c);
-                }
-                KeyCode::Backspace => {
-                    self.input_buffer.pop();
-                }
                 KeyCode::Enter => {
                     let _ = std::io::stdout().flush();
 
-                    if self.input_buffer.len() != 0 {
-                        match File::create(self.input_buffer.clone()) {
-                            Ok(mut file) => {
-                                file.write_all(String::from("").as_bytes())?;
+                    if !self.input_buffer.is_empty() {
+                        // 末尾が'/'ならディレクトリ
+                        let is_dir = matches!(self.mode, Mode::Adddir)
+                            || self.input_buffer.as_str().ends_with('/');
+                        let path = self.input_path();
+                        let template = if is_dir {
+                            None
+                        } else {
+                            config::template_for(&path)
+                        };
+                        match fileops::create_entry(&path, is_dir, template.as_deref()) {
+                            Ok(created) => {
+                                self.journal
+                                    .record(vec![Operation::create(created.clone())]);
+                                self.render_dir_view()?;
+                                // 作ったもの (または作ったものを含むディレクトリ) にフォーカス
+                                if let Ok(relative) = created.strip_prefix(&self.pwd) {
+                                    if let Some(name) = relative.iter().next() {
+                                        self.focus_name(&name.to_string_lossy());
+                                    }
+                                }
                             }
-                            Err(_) => {
-                                fs::create_dir_all(self.input_buffer.clone())?;
+                            Err(e) => {
+                                self.message = e.to_string();
                             }
-                        };
-
+                        }
                         self.input_buffer.clear();
                     }
                     self.mode = Mode::Nomal;
-
-                    self.render_dir_view()?;
                 }
 
                 _ => {}
@@ -361,79 +814,1012 @@ impl App {
         Ok(())
     }
 
-    fn is_dir(&mut self, name: String) -> bool {
-        let path = Path::new(name.as_str());
-
-        // ファイルかどうかを判定
-        if path.is_file() {
-            false
-        } else {
-            // ディレクトリかどうかを判定
-            if path.is_dir() {
-                true
-            } else {
-                println!("{} はファイルでもディレクトリでもありません", name);
-                false
+    // フォーカスをnameの位置に合わせる
+    fn focus_name(&mut self, name: &str) {
+        for (page_index, page) in self.in_dir_files.iter().enumerate() {
+            if let Some(index) = page.iter().position(|entry| entry == name) {
+                self.focus_page = page_index;
+                self.focus_index = index;
+                return;
             }
         }
     }
 
-    fn is_file(&mut self, name: String) -> bool {
-        let path = Path::new(name.as_str());
+    fn open_in_editor(&mut self, files: Vec<String>) -> Result<()> {
+        let mut words = self.config.editor.split_whitespace();
+        let program = words.next().unwrap_or("nvim").to_string();
+        let args: Vec<String> = words.map(|word| word.to_string()).collect();
+
+        self.suspend()?;
+        let result = Command::new(program)
+            .args(args)
+            .args(files)
+            .spawn()
+            .and_then(|mut child| child.wait());
+        self.resume()?;
+        result.map(|_| ())
+    }
 
-        // ファイルかどうかを判定
-        if path.is_file() {
-            true
-        } else {
-            // ディレクトリかどうかを判定
-            if path.is_dir() {
-                false
-            } else {
-                println!("{} はファイルでもディレクトリでもありません", name);
-                false
+    // 外部のプログラムに端末を明け渡す
+    fn suspend(&mut self) -> Result<()> {
+        execute!(
+            std::io::stderr(),
+            DisableBracketedPaste,
+            Show,
+            LeaveAlternateScreen
+        )?;
+        disable_raw_mode()
+    }
+
+    fn resume(&mut self) -> Result<()> {
+        enable_raw_mode()?;
+        execute!(
+            std::io::stderr(),
+            Hide,
+            EnterAlternateScreen,
+            EnableBracketedPaste
+        )
+    }
+
+    fn shell() -> String {
+        env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string())
+    }
+
+    // :!command -> $SHELL -c で実行し、出力を読めるようにキーが押されるまで待つ
+    // capture_output のときは出力を枠に表示する
+    fn run_shell(&mut self, command: &str) -> Result<()> {
+        if self.config.capture_output {
+            return self.capture_shell(command);
+        }
+        self.suspend()?;
+        let status = Command::new(Self::shell())
+            .arg("-c")
+            .arg(command)
+            .current_dir(&self.pwd)
+            .status();
+        match &status {
+            Ok(status) if status.success() => eprint!("\n[done] "),
+            Ok(status) => eprint!("\n[{}] ", status),
+            Err(e) => eprint!("\n[{}] ", e),
+        }
+        eprint!("press any key to continue");
+        enable_raw_mode()?;
+        while !matches!(read()?, Event::Key(_)) {}
+        self.resume()?;
+
+        self.render_dir_view()?;
+        match status {
+            Ok(status) if !status.success() => {
+                self.message = format!("{}: {}", command, status);
+                Ok(())
             }
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
         }
     }
 
-    fn remove_accept(&mut self) -> Result<()> {
-        let rm_something = self.in_dir_files[self.focus_page][self.focus_index].clone();
-        if self.is_dir(rm_something.clone()) {
-            fs::remove_dir_all(rm_something)?;
-        } else if self.is_file(rm_something.clone()) {
-            fs::remove_file(rm_something)?;
+    fn capture_shell(&mut self, command: &str) -> Result<()> {
+        // 標準エラー出力も同じ順番で受け取る
+        let output = Command::new(Self::shell())
+            .arg("-c")
+            .arg(format!("exec 2>&1\n{}", command))
+            .current_dir(&self.pwd)
+            .stdin(Stdio::null())
+            .output()?;
+        self.render_dir_view()?;
+
+        let text = String::from_utf8_lossy(&output.stdout);
+        if text.is_empty() {
+            if !output.status.success() {
+                self.message = format!("{}: {}", command, output.status);
+            }
+            return Ok(());
         }
-        self.get_in_dir()?;
+        if text.lines().count() > output::PAGER_THRESHOLD {
+            return self.page(&text);
+        }
+        self.output = Some(Output::new(
+            command,
+            output::status_label(&output.status),
+            &text,
+        ));
+        self.mode = Mode::Output;
         Ok(())
     }
 
-    fn remove_file_or_directory(&mut self) -> Result<()> {
-        if let Event::Key(KeyEvent { code, .. }) = read()? {
-            match code {
-                KeyCode::Esc => {
-                    self.mode = Mode::Nomal;
+    // ページャの標準入力に流し込む
+    fn page(&mut self, text: &str) -> Result<()> {
+        let mut words = self.config.pager.split_whitespace();
+        let program = words.next().unwrap_or("less").to_string();
+        let args: Vec<String> = words.map(|word| word.to_string()).collect();
+
+        self.suspend()?;
+        let result = Command::new(program)
+            .args(args)
+            .current_dir(&self.pwd)
+            .stdin(Stdio::piped())
+            .spawn()
+            .and_then(|mut child| {
+                if let Some(mut stdin) = child.stdin.take() {
+                    // ページャを途中で閉じると書き込みに失敗するが問題ない
+                    let _ = stdin.write_all(text.as_bytes());
                 }
-                KeyCode::Char('n') => {
+                child.wait()
+            });
+        self.resume()?;
+        result.map(|_| ())
+    }
+
+    // 出力の枠 ------------------------------------------------------------------------------
+
+    fn output_height(&self) -> usize {
+        self.window_height as usize - 6
+    }
+
+    fn output_key_read(&mut self) -> Result<()> {
+        let height = self.output_height();
+        if let Event::Key(KeyEvent {
+            code, modifiers, ..
+        }) = read()?
+        {
+            self.message.clear();
+            let output = match self.output.as_mut() {
+                Some(output) => output,
+                None => {
                     self.mode = Mode::Nomal;
+                    return Ok(());
                 }
-                KeyCode::Char('y') => {
+            };
+            let half = (height / 2).max(1) as isize;
+            match code {
+                KeyCode::Char('d') if modifiers.contains(KeyModifiers::CONTROL) => {
+                    output.scroll_by(half, height)
+                }
+                KeyCode::Char('u') if modifiers.contains(KeyModifiers::CONTROL) => {
+                    output.scroll_by(-half, height)
+                }
+                KeyCode::Char('j') | KeyCode::Down => output.scroll_by(1, height),
+                KeyCode::Char('k') | KeyCode::Up => output.scroll_by(-1, height),
+                KeyCode::Char(' ') | KeyCode::PageDown => output.scroll_by(height as isize, height),
+                KeyCode::PageUp => output.scroll_by(-(height as isize), height),
+                KeyCode::Char('g') | KeyCode::Home => output.scroll_by(isize::MIN, height),
+                KeyCode::Char('G') | KeyCode::End => output.scroll_to_end(height),
+                KeyCode::Char('n') | KeyCode::Char('N') => {
+                    if !output.search(code == KeyCode::Char('n')) {
+                        self.message = format!("not found: {}", output.query());
+                    }
+                }
+                KeyCode::Char('/') => {
+                    self.input_buffer.clear();
+                    self.mode = Mode::OutputSearch;
+                }
+                KeyCode::Char('p') => {
+                    let text = output.text();
+                    self.page(&text)?;
+                }
+                KeyCode::Esc | KeyCode::Char('q') => {
+                    self.output = None;
                     self.mode = Mode::Nomal;
-                    self.remove_accept()?;
                 }
                 _ => {}
             }
         }
-
         Ok(())
     }
 
-    pub fn key_read(&mut self, max_down: usize) -> Result<()> {
-        // ------------------------------------------------------------------------------------
-        return match self.mode {
-            Mode::Nomal => self.nomal_key_read(max_down),
-            Mode::Command => self.command_key_read(),
-            Mode::Cd => self.change_directory(),
-            Mode::Addfile => self.add_new_file_or_directory(),
-            Mode::Delfile => self.remove_file_or_directory(),
+    fn output_search_key_read(&mut self) -> Result<()> {
+        if let Some(code) = self.read_prompt_key()? {
+            match code {
+                KeyCode::Esc => {
+                    self.input_buffer.clear();
+                    self.mode = Mode::Output;
+                }
+                KeyCode::Enter => {
+                    self.mode = Mode::Output;
+                    if let Some(output) = self.output.as_mut() {
+                        output.set_query(self.input_buffer.as_str());
+                        if !output.query().is_empty() && !output.search(true) {
+                            self.message = format!("not found: {}", output.query());
+                        }
+                    }
+                    self.input_buffer.clear();
+                }
+
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_output(&mut self) -> Result<()> {
+        let height = self.output_height();
+        let width = self.window_width as usize - 5;
+        let output = match &self.output {
+            Some(output) => output,
+            None => return Ok(()),
+        };
+
+        let mut lines: Vec<(Color, String)> = output
+            .lines()
+            .iter()
+            .skip(output.scroll())
+            .take(height)
+            .map(|line| {
+                let color = if output.is_match(line) {
+                    Color::Yellow
+                } else {
+                    Color::Blue
+                };
+                (color, truncate_to_width(line, width))
+            })
+            .collect();
+        while lines.len() < height {
+            lines.push((Color::Blue, String::new()));
+        }
+        if self.message.is_empty() {
+            lines.push((
+                Color::Blue,
+                "j/k: scroll  /: search  n/N: next/prev  p: pager  q: close".to_string(),
+            ));
+        } else {
+            lines.push((Color::Yellow, self.message.clone()));
+        }
+        let title = format!(
+            "{}-[{}-{}/{}]",
+            output.title(width / 2),
+            output.scroll() + 1,
+            (output.scroll() + height).min(output.lines().len()),
+            output.lines().len()
+        );
+
+        let mut window = Window::new()
+            .set_mode(Mode::Nomal)
+            .set_width(self.window_width as usize - 2);
+        window = window
+            .set_start_hight(self.window_height as usize - lines.len())
+            .set_hight(lines.len());
+        window.set_title(title).top_line()?;
+        for (color, line) in lines {
+            window.set_color(color).put(line)?;
+        }
+
+        if let Mode::OutputSearch = self.mode {
+            queue!(
+                std::io::stderr(),
+                SetBackgroundColor(GRUVBOX_BACKGROUND),
+                MoveTo(self.start_w, self.start_h + self.window_height)
+            )?;
+            self.draw_input_line()?;
+        }
+        Ok(())
+    }
+    // 出力の枠 ------------------------------------------------------------------------------
+
+    fn rename_key_read(&mut self) -> Result<()> {
+        if let Some(code) = self.read_prompt_key()? {
+            match code {
+                KeyCode::Esc => {
+                    self.input_buffer.clear();
+                    self.mode = Mode::Nomal;
+                }
+                KeyCode::Enter => {
+                    self.mode = Mode::Nomal;
+                    let new_name = self.input_buffer.as_str().trim().to_string();
+                    match self.rename_accept(&new_name) {
+                        Ok(_) => {
+                            self.render_dir_view()?;
+                            self.focus_name(&new_name);
+                        }
+                        Err(e) => {
+                            self.message = e.to_string();
+                        }
+                    }
+                    self.input_buffer.clear();
+                }
+
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn rename_accept(&mut self, new_name: &str) -> Result<()> {
+        let old_name = match self.focused_name() {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+        if !rename::is_valid_name(new_name) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid name: \"{}\"", new_name),
+            ));
+        }
+        if old_name == new_name {
+            return Ok(());
+        }
+
+        let new_path = self.absolute_path(new_name);
+        if fs::symlink_metadata(&new_path).is_ok() {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("\"{}\" already exists", new_name),
+            ));
+        }
+        let old_path = self.absolute_path(&old_name);
+        fs::rename(&old_path, &new_path)?;
+        self.journal.record(vec![Operation::Rename {
+            from: PathBuf::from(old_path),
+            to: PathBuf::from(new_path),
+        }]);
+        Ok(())
+    }
+
+    // 対象の名前を一時ファイルに書き出してエディタで編集してもらう
+    fn bulk_rename(&mut self) -> Result<()> {
+        let sources: Vec<PathBuf> = self.targets().iter().map(PathBuf::from).collect();
+        if sources.is_empty() {
+            return Ok(());
+        }
+        let names: Vec<String> = sources
+            .iter()
+            .map(|src| {
+                src.file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        if names.iter().any(|name| name.contains('\n')) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "cannot bulk rename names containing newlines",
+            ));
+        }
+
+        let list_path = env::temp_dir().join(format!("lsl-rename-{}.txt", std::process::id()));
+        fs::write(&list_path, format!("{}\n", names.join("\n")))?;
+        let edited = self
+            .open_in_editor(vec![list_path.display().to_string()])
+            .and_then(|_| fs::read_to_string(&list_path));
+        let _ = fs::remove_file(&list_path);
+
+        let mut new_names: Vec<String> = edited?.lines().map(|line| line.to_string()).collect();
+        while new_names.last().is_some_and(|line| line.trim().is_empty()) {
+            new_names.pop();
+        }
+
+        self.rename_plan = rename::plan(&sources, &new_names)?;
+        if self.rename_plan.is_empty() {
+            self.message = "nothing to rename".to_string();
+        } else {
+            self.mode = Mode::BulkRename;
+        }
+        Ok(())
+    }
+
+    fn bulk_rename_key_read(&mut self) -> Result<()> {
+        if let Event::Key(KeyEvent { code, .. }) = read()? {
+            match code {
+                KeyCode::Esc | KeyCode::Char('n') => {
+                    self.rename_plan.clear();
+                    self.mode = Mode::Nomal;
+                }
+                KeyCode::Char('y') => {
+                    self.mode = Mode::Nomal;
+                    match rename::apply(&self.rename_plan) {
+                        Ok(_) => {
+                            self.message = format!("renamed {} item(s)", self.rename_plan.len());
+                            self.marked_files.clear();
+                            self.journal.record(
+                                self.rename_plan
+                                    .iter()
+                                    .map(|(from, to)| Operation::Rename {
+                                        from: from.clone(),
+                                        to: to.clone(),
+                                    })
+                                    .collect(),
+                            );
+                        }
+                        Err(e) => {
+                            self.message = e.to_string();
+                        }
+                    }
+                    self.rename_plan.clear();
+                    self.render_dir_view()?;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_dir(&mut self, name: String) -> bool {
+        let path = Path::new(name.as_str());
+
+        // ファイルかどうかを判定
+        if path.is_file() {
+            false
+        } else {
+            // ディレクトリかどうかを判定
+            if path.is_dir() {
+                true
+            } else {
+                println!("{} はファイルでもディレクトリでもありません", name);
+                false
+            }
+        }
+    }
+
+    fn is_file(&mut self, name: String) -> bool {
+        let path = Path::new(name.as_str());
+
+        // ファイルかどうかを判定
+        if path.is_file() {
+            true
+        } else {
+            // ディレクトリかどうかを判定
+            if path.is_dir() {
+                false
+            } else {
+                println!("{} はファイルでもディレクトリでもありません", name);
+                false
+            }
+        }
+    }
+
+    fn focused_name(&self) -> Option<String> {
+        self.in_dir_files
+            .get(self.focus_page)?
+            .get(self.focus_index)
+            .cloned()
+    }
+
+    fn absolute_path(&self, name: &str) -> String {
+        Path::new(&self.pwd).join(name).display().to_string()
+    }
+
+    // ページをまたいだ通し番号
+    fn flat_focus_index(&self) -> usize {
+        self.in_dir_files[..self.focus_page]
+            .iter()
+            .map(|page| page.len())
+            .sum::<usize>()
+            + self.focus_index
+    }
+
+    fn is_marked(&self, name: &str) -> bool {
+        self.marked_files.contains(&self.absolute_path(name))
+    }
+
+    fn mark(&mut self, name: &str) {
+        if !self.is_marked(name) {
+            self.marked_files.push(self.absolute_path(name));
+        }
+    }
+
+    fn toggle_mark(&mut self) {
+        if let Some(name) = self.focused_name() {
+            if self.is_marked(&name) {
+                let path = self.absolute_path(&name);
+                self.marked_files.retain(|marked| *marked != path);
+            } else {
+                self.mark(&name);
+            }
+        }
+    }
+
+    // 1回目で始点を記録し、2回目で始点から現在位置までをマーク
+    fn range_mark(&mut self) {
+        let focus = self.flat_focus_index();
+        match self.mark_anchor.take() {
+            None => {
+                self.mark_anchor = Some(focus);
+                self.message = "range start (press V again to mark)".to_string();
+            }
+            Some(anchor) => {
+                let (from, to) = if anchor < focus {
+                    (anchor, focus)
+                } else {
+                    (focus, anchor)
+                };
+                let names: Vec<String> = self.in_dir_files.concat();
+                for name in names.iter().take(to + 1).skip(from) {
+                    self.mark(name);
+                }
+            }
+        }
+    }
+
+    fn mark_all(&mut self) {
+        for name in self.in_dir_files.concat() {
+            self.mark(&name);
+        }
+    }
+
+    fn invert_marks(&mut self) {
+        for name in self.in_dir_files.concat() {
+            let path = self.absolute_path(&name);
+            if self.marked_files.contains(&path) {
+                self.marked_files.retain(|marked| *marked != path);
+            } else {
+                self.marked_files.push(path);
+            }
+        }
+    }
+
+    fn glob_matches(&self, pattern: &str) -> Vec<String> {
+        self.in_dir_files
+            .concat()
+            .into_iter()
+            .filter(|name| glob::is_match(pattern, name))
+            .collect()
+    }
+
+    // 操作の対象 -> マークがあればマークしたもの全て、なければフォーカス中のもの
+    fn targets(&self) -> Vec<String> {
+        if !self.marked_files.is_empty() {
+            return self.marked_files.clone();
+        }
+        match self.focused_name() {
+            Some(name) => vec![self.absolute_path(&name)],
+            None => vec![],
+        }
+    }
+
+    fn targets_label(&self) -> String {
+        let targets = self.targets();
+        match targets.len() {
+            1 => format!(
+                "\"{}\"",
+                Path::new(&targets[0])
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+            ),
+            n => format!("{} items", n),
+        }
+    }
+
+    fn remove_accept(&mut self) -> Result<()> {
+        for rm_something in self.targets() {
+            if self.is_dir(rm_something.clone()) {
+                fs::remove_dir_all(rm_something)?;
+            } else if self.is_file(rm_something.clone()) {
+                fs::remove_file(rm_something)?;
+            }
+        }
+        self.marked_files.clear();
+        self.get_in_dir()?;
+        Ok(())
+    }
+
+    fn input_path(&self) -> PathBuf {
+        Path::new(&self.pwd).join(complete::expand(self.input_buffer.as_str().trim()))
+    }
+
+    fn yank(&mut self, cut: bool) {
+        self.clipboard = self.targets();
+        self.clipboard_cut = cut;
+        self.marked_files.clear();
+        self.message = format!(
+            "{} {} item(s)",
+            if cut { "cut" } else { "yanked" },
+            self.clipboard.len()
+        );
+    }
+
+    // コピー・移動先が既存のディレクトリでなければ、対象は1つでなければならない
+    fn start_transfer(
+        &mut self,
+        sources: Vec<String>,
+        destination: PathBuf,
+        is_move: bool,
+    ) -> Result<()> {
+        if sources.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "nothing to paste"));
+        }
+        if sources.len() > 1 && !destination.is_dir() {
+            return Err(Error::new(
+                ErrorKind::NotADirectory,
+                format!("{} is not a directory", destination.display()),
+            ));
+        }
+
+        self.transfer = Some(Transfer::new(
+            sources.iter().map(PathBuf::from).collect(),
+            &destination,
+            is_move,
+        ));
+        self.mode = Mode::Transfer;
+        Ok(())
+    }
+
+    fn finish_transfer(&mut self) -> Result<()> {
+        if let Some(transfer) = self.transfer.take() {
+            let (done, _) = transfer.items();
+            self.journal.record(
+                transfer
+                    .completed()
+                    .iter()
+                    .map(|(from, to)| {
+                        if transfer.is_move() {
+                            Operation::Move {
+                                from: from.clone(),
+                                to: to.clone(),
+                            }
+                        } else {
+                            Operation::copy(to.clone())
+                        }
+                    })
+                    .collect(),
+            );
+            if transfer.is_move() && transfer.is_done() {
+                // 移動し終わったものは貼り付け元に残っていない
+                self.clipboard.clear();
+            }
+            if self.message.is_empty() {
+                self.message = format!("{} item(s) done", done);
+            }
+        }
+        self.mode = Mode::Nomal;
+        self.render_dir_view()
+    }
+
+    // 少しずつ進めて、合間にEscが押されていたら中断
+    fn transfer_key_read(&mut self) -> Result<()> {
+        if poll(Duration::ZERO)? {
+            if let Event::Key(KeyEvent {
+                code: KeyCode::Esc, ..
+            }) = read()?
+            {
+                if let Some(transfer) = self.transfer.as_mut() {
+                    transfer.cancel();
+                }
+                self.message = "transfer cancelled".to_string();
+                return self.finish_transfer();
+            }
+            return Ok(());
+        }
+
+        let started = Instant::now();
+        while started.elapsed() < Duration::from_millis(50) {
+            let transfer = match self.transfer.as_mut() {
+                Some(transfer) => transfer,
+                None => return self.finish_transfer(),
+            };
+            if let Err(e) = transfer.step() {
+                transfer.cancel();
+                self.message = e.to_string();
+                return self.finish_transfer();
+            }
+            if transfer.conflict().is_some() {
+                self.mode = Mode::Conflict;
+                return Ok(());
+            }
+            if transfer.is_done() {
+                return self.finish_transfer();
+            }
+        }
+        Ok(())
+    }
+
+    fn conflict_key_read(&mut self) -> Result<()> {
+        if let Event::Key(KeyEvent { code, .. }) = read()? {
+            let (resolution, apply_to_all) = match code {
+                KeyCode::Char('o') => (Resolution::Overwrite, false),
+                KeyCode::Char('s') => (Resolution::Skip, false),
+                KeyCode::Char('r') => (Resolution::Rename, false),
+                KeyCode::Char('O') => (Resolution::Overwrite, true),
+                KeyCode::Char('S') => (Resolution::Skip, true),
+                KeyCode::Char('R') => (Resolution::Rename, true),
+                KeyCode::Esc => {
+                    if let Some(transfer) = self.transfer.as_mut() {
+                        transfer.cancel();
+                    }
+                    self.message = "transfer cancelled".to_string();
+                    return self.finish_transfer();
+                }
+                _ => return Ok(()),
+            };
+            if let Some(transfer) = self.transfer.as_mut() {
+                if let Err(e) = transfer.resolve(resolution, apply_to_all) {
+                    transfer.cancel();
+                    self.message = e.to_string();
+                    return self.finish_transfer();
+                }
+            }
+            self.mode = Mode::Transfer;
+        }
+        Ok(())
+    }
+
+    fn chmod_accept(&mut self) -> Result<()> {
+        let mode = command::parse_mode(self.input_buffer.as_str())?;
+        let targets = self.targets();
+        self.chmod_paths(mode, targets)
+    }
+
+    fn chmod_paths(&mut self, mode: u32, targets: Vec<String>) -> Result<()> {
+        let mut operations = Vec::new();
+        for target in targets {
+            let old_mode = fs::symlink_metadata(&target)?.permissions().mode() & 0o7777;
+            if let Err(e) = fileops::chmod(Path::new(&target), mode) {
+                self.journal.record(operations);
+                return Err(e);
+            }
+            operations.push(Operation::Chmod {
+                path: PathBuf::from(target),
+                old_mode,
+                new_mode: mode,
+            });
+        }
+        self.journal.record(operations);
+        self.marked_files.clear();
+        Ok(())
+    }
+
+    // 選択範囲に対する操作の入力 (glob / copy / move / chmod)
+    fn bulk_operation_key_read(&mut self) -> Result<()> {
+        if let Some(code) = self.read_prompt_key()? {
+            if matches!(self.mode, Mode::Copy | Mode::Move) {
+                self.complete_key(code);
+            }
+            match code {
+                KeyCode::Esc => {
+                    self.input_buffer.clear();
+                    self.mode = Mode::Nomal;
+                }
+                KeyCode::Enter => {
+                    let mode = self.mode;
+                    self.mode = Mode::Nomal;
+                    let result = match mode {
+                        Mode::Glob => {
+                            for name in self.glob_matches(self.input_buffer.as_str()) {
+                                self.mark(&name);
+                            }
+                            Ok(())
+                        }
+                        Mode::Copy | Mode::Move => {
+                            let targets = self.targets();
+                            self.marked_files.clear();
+                            self.start_transfer(
+                                targets,
+                                self.input_path(),
+                                matches!(mode, Mode::Move),
+                            )
+                        }
+                        Mode::Chmod => self.chmod_accept(),
+                        _ => Ok(()),
+                    };
+                    if let Err(e) = result {
+                        self.message = e.to_string();
+                    }
+                    self.input_buffer.clear();
+                    self.render_dir_view()?;
+                }
+
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    // ゴミ箱へ移す
+    fn trash_accept(&mut self) -> Result<()> {
+        let targets = self.targets();
+        self.trash_paths(targets)
+    }
+
+    fn trash_paths(&mut self, targets: Vec<String>) -> Result<()> {
+        let mut operations = Vec::new();
+        for target in targets {
+            match trash::trash(Path::new(&target)) {
+                Ok(entry) => operations.push(Operation::Trash {
+                    original: entry.original,
+                    name: entry.name,
+                }),
+                Err(e) => {
+                    self.journal.record(operations);
+                    return Err(e);
+                }
+            }
+        }
+        self.journal.record(operations);
+        self.marked_files.clear();
+        self.get_in_dir()?;
+        Ok(())
+    }
+
+    fn remove_file_or_directory(&mut self) -> Result<()> {
+        if let Event::Key(KeyEvent { code, .. }) = read()? {
+            match code {
+                KeyCode::Esc => {
+                    self.mode = Mode::Nomal;
+                }
+                KeyCode::Char('n') => {
+                    self.mode = Mode::Nomal;
+                }
+                KeyCode::Char('y') => {
+                    self.mode = Mode::Nomal;
+                    if let Err(e) = self.trash_accept() {
+                        self.message = e.to_string();
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    // 完全に削除 -> 取り消せないので "yes" と打ち込んでもらう
+    fn purge_key_read(&mut self) -> Result<()> {
+        if let Some(code) = self.read_prompt_key()? {
+            match code {
+                KeyCode::Esc => {
+                    self.input_buffer.clear();
+                    self.mode = Mode::Nomal;
+                }
+                KeyCode::Enter => {
+                    self.mode = Mode::Nomal;
+                    if self.input_buffer.as_str() == "yes" {
+                        if let Err(e) = self.remove_accept() {
+                            self.message = e.to_string();
+                        }
+                    } else {
+                        self.message = "not deleted".to_string();
+                    }
+                    self.input_buffer.clear();
+                }
+
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn trash_key_read(&mut self) -> Result<()> {
+        if let Event::Key(KeyEvent { code, .. }) = read()? {
+            self.message.clear();
+            match code {
+                KeyCode::Esc | KeyCode::Char('q') => {
+                    self.trash_entries.clear();
+                    self.mode = Mode::Nomal;
+                    self.render_dir_view()?;
+                }
+                KeyCode::Char('j') => {
+                    if self.trash_focus + 1 < self.trash_entries.len() {
+                        self.trash_focus += 1;
+                    }
+                }
+                KeyCode::Char('k') => {
+                    self.trash_focus = self.trash_focus.saturating_sub(1);
+                }
+                KeyCode::Char('r') => {
+                    if let Some(entry) = self.trash_entries.get(self.trash_focus) {
+                        match trash::restore(entry) {
+                            Ok(_) => {
+                                self.message = format!("restored {}", entry.original.display())
+                            }
+                            Err(e) => self.message = e.to_string(),
+                        }
+                        self.reload_trash();
+                    }
+                }
+                KeyCode::Char('x') => {
+                    if !self.trash_entries.is_empty() {
+                        self.mode = Mode::TrashPurge;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn trash_purge_key_read(&mut self) -> Result<()> {
+        if let Event::Key(KeyEvent { code, .. }) = read()? {
+            match code {
+                KeyCode::Char('y') => {
+                    if let Some(entry) = self.trash_entries.get(self.trash_focus) {
+                        if let Err(e) = trash::purge(entry) {
+                            self.message = e.to_string();
+                        }
+                    }
+                    self.reload_trash();
+                    self.mode = Mode::Trash;
+                }
+                KeyCode::Esc | KeyCode::Char('n') => {
+                    self.mode = Mode::Trash;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<()> {
+        self.message = match self.journal.undo() {
+            Ok(description) => format!("undo: {}", description),
+            Err(e) => e.to_string(),
+        };
+        self.render_dir_view()
+    }
+
+    fn redo(&mut self) -> Result<()> {
+        self.message = match self.journal.redo() {
+            Ok(description) => format!("redo: {}", description),
+            Err(e) => e.to_string(),
+        };
+        self.render_dir_view()
+    }
+
+    // 新しいものが上
+    fn history_key_read(&mut self) -> Result<()> {
+        if let Event::Key(KeyEvent {
+            code, modifiers, ..
+        }) = read()?
+        {
+            self.message.clear();
+            match code {
+                KeyCode::Char('r') if modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.redo()?;
+                }
+                KeyCode::Char('u') => {
+                    self.undo()?;
+                }
+                KeyCode::Esc | KeyCode::Char('q') => {
+                    self.mode = Mode::Nomal;
+                }
+                KeyCode::Char('j') => {
+                    if self.history_focus + 1 < self.journal.records().len() {
+                        self.history_focus += 1;
+                    }
+                }
+                KeyCode::Char('k') => {
+                    self.history_focus = self.history_focus.saturating_sub(1);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn reload_trash(&mut self) {
+        self.trash_entries = trash::list();
+        if self.trash_focus >= self.trash_entries.len() {
+            self.trash_focus = self.trash_entries.len().saturating_sub(1);
+        }
+    }
+
+    pub fn key_read(&mut self, max_down: usize) -> Result<()> {
+        // ------------------------------------------------------------------------------------
+        return match self.mode {
+            Mode::Nomal => self.nomal_key_read(max_down),
+            Mode::Command => self.command_key_read(),
+            Mode::Cd => self.change_directory(),
+            Mode::Addfile | Mode::Adddir => self.add_new_file_or_directory(),
+            Mode::Delfile => self.remove_file_or_directory(),
+            Mode::Glob | Mode::Copy | Mode::Move | Mode::Chmod => self.bulk_operation_key_read(),
+            Mode::Transfer => self.transfer_key_read(),
+            Mode::Conflict => self.conflict_key_read(),
+            Mode::Rename => self.rename_key_read(),
+            Mode::BulkRename => self.bulk_rename_key_read(),
+            Mode::Purge => self.purge_key_read(),
+            Mode::Trash => self.trash_key_read(),
+            Mode::TrashPurge => self.trash_purge_key_read(),
+            Mode::History => self.history_key_read(),
+            Mode::Help => self.help_key_read(),
+            Mode::Jobs => self.jobs_key_read(),
+            Mode::Output => self.output_key_read(),
+            Mode::OutputSearch => self.output_search_key_read(),
             Mode::Edit => Ok(()),
             _ => Ok(()),
         };
@@ -544,6 +1930,14 @@ impl App {
             text_line.unfocus()?;
         }
 
+        // mark
+        text_line.create_text_box(Color::Magenta, 2, 1);
+        text_line.put(if self.is_marked(&draw_data) {
+            String::from("* ")
+        } else {
+            String::from("  ")
+        })?;
+
         // file permission
         let metadata = fs::symlink_metadata(draw_data.clone()).expect("Failed to get metadata");
         let hex_permission = self.shinsu(metadata.permissions().mode() as i64, 8);
@@ -613,18 +2007,6 @@ impl App {
         Ok(())
     }
 
-    fn find_dir(&mut self, serch_word: String, directory_vec: Vec<String>) -> Vec<String> {
-        let mut return_vec = vec![];
-
-        for i in directory_vec {
-            if i.starts_with(&serch_word) {
-                return_vec.push(i);
-            }
-        }
-
-        return_vec
-    }
-
     fn nomal_ui(&mut self, print_strings: Vec<String>) -> Result<()> {
         queue!(std::io::stderr(), MoveTo(self.start_w, self.start_h))?;
         queue!(
@@ -664,6 +2046,19 @@ impl App {
             .create_text_box(Color::Blue, 1, 1)
             .put("]".to_string())?;
 
+        if !self.marked_files.is_empty() {
+            let marked_char = format!("{} marked", self.marked_files.len());
+            text_line
+                .create_text_box(Color::Blue, 2, 1)
+                .put("-[".to_string())?;
+            text_line
+                .create_text_box(Color::Magenta, marked_char.len(), 1)
+                .put(marked_char)?;
+            text_line
+                .create_text_box(Color::Blue, 1, 1)
+                .put("]".to_string())?;
+        }
+
         text_line.blank()?;
 
         queue!(std::io::stderr(), Print("┐"))?;
@@ -688,111 +2083,577 @@ impl App {
 
             // 枠の中身-------------------------------------------------------------------------------
 
-            let _ = self.draw_line(print_strings[i].clone(), i);
-            // --------------------------------------------------------------------------------------
+            let _ = self.draw_line(print_strings[i].clone(), i);
+            // --------------------------------------------------------------------------------------
+
+            queue!(std::io::stderr(), SetForegroundColor(Color::Blue),)?;
+            self.cursor.0 = self.window_width - 1;
+
+            queue!(
+                std::io::stderr(),
+                MoveToColumn((self.cursor.0 + self.start_w).try_into().unwrap()),
+                Print("│"),
+                MoveTo(
+                    self.start_w,
+                    (i as u16 + self.cursor.1 + self.start_h + 1)
+                        .try_into()
+                        .unwrap()
+                )
+            )?;
+        }
+        if print_strings.len() > 0 {
+            self.cursor.1 += print_strings.len() as u16 - 1;
+        }
+
+        queue!(
+            std::io::stderr(),
+            MoveToRow(self.cursor.1 + self.start_h + 2),
+        )?;
+
+        // 何も表示することがない場合の空欄
+        for i in if print_strings.len() > self.window_height as usize - 2 {
+            0..0
+        } else {
+            self.cursor.1..self.window_height - 1
+        } {
+            queue!(std::io::stderr(), Print("│"))?;
+            for _ in 1..self.window_width - 1 {
+                queue!(std::io::stderr(), Print(" "))?;
+            }
+            queue!(
+                std::io::stderr(),
+                Print("│"),
+                MoveTo(
+                    self.start_w,
+                    (i as u16 + self.start_h + 1).try_into().unwrap()
+                )
+            )?;
+        }
+
+        // 下の枠
+        queue!(std::io::stderr(), Print("└"))?;
+
+        for _ in 1..self.window_width - 1 {
+            queue!(std::io::stderr(), Print("─"))?;
+        }
+        queue!(std::io::stderr(), Print("┘"))?;
+        // -------------------------
+        Ok(())
+    }
+
+    pub fn ui(&mut self, print_strings: Vec<String>) -> Result<()> {
+        self.nomal_ui(print_strings)?;
+
+        match self.mode {
+            Mode::Nomal => {
+                if !self.message.is_empty() {
+                    self.draw_message()?;
+                }
+            }
+            Mode::Edit => {}
+            Mode::Cd => {
+                let (auto_correct, selected) = self.completion_list();
+                self.draw_auto_correct(auto_correct, selected, "[file open]")?;
+            }
+            Mode::Command => {
+                if !self.message.is_empty() {
+                    self.draw_command_error()?;
+                } else {
+                    let (candidates, selected) = self.completion_list();
+                    if candidates.is_empty() || self.input_buffer.is_empty() {
+                        self.draw_command_window("[command mode]")?;
+                    } else {
+                        self.draw_auto_correct(candidates, selected, "[command mode]")?;
+                    }
+                }
+            }
+            Mode::Addfile | Mode::Adddir => {
+                let (auto_correct, selected) = self.completion_list();
+                let title = if let Mode::Adddir = self.mode {
+                    "[add new directory]"
+                } else {
+                    "[add new file]"
+                };
+                self.draw_auto_correct(auto_correct, selected, title)?;
+            }
+            Mode::Delfile => {
+                self.draw_remove_file()?;
+            }
+            Mode::Glob => {
+                let matches = self.glob_matches(self.input_buffer.as_str());
+                self.draw_auto_correct(matches, None, "[select by glob]")?;
+            }
+            Mode::Copy | Mode::Move => {
+                let (auto_correct, selected) = self.completion_list();
+                let title = format!(
+                    "[{} {} to]",
+                    if let Mode::Copy = self.mode {
+                        "copy"
+                    } else {
+                        "move"
+                    },
+                    self.targets_label()
+                );
+                self.draw_auto_correct(auto_correct, selected, &title)?;
+            }
+            Mode::Chmod => {
+                let title = format!("[chmod {}]", self.targets_label());
+                self.draw_command_window(&title)?;
+            }
+            Mode::Transfer => {
+                self.draw_transfer()?;
+            }
+            Mode::Conflict => {
+                self.draw_conflict()?;
+            }
+            Mode::Rename => {
+                self.draw_command_window("[rename]")?;
+            }
+            Mode::BulkRename => {
+                self.draw_rename_preview()?;
+            }
+            Mode::Purge => {
+                let title = format!(
+                    "[permanently delete {} ? type \"yes\"]",
+                    self.targets_label()
+                );
+                self.draw_command_window(&title)?;
+            }
+            Mode::Trash | Mode::TrashPurge => {
+                self.draw_trash()?;
+            }
+            Mode::History => {
+                self.draw_history()?;
+            }
+            Mode::Help => {
+                self.draw_help()?;
+            }
+            Mode::Jobs => {
+                self.draw_jobs()?;
+            }
+            Mode::Output | Mode::OutputSearch => {
+                self.draw_output()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn draw_remove_file(&mut self) -> Result<()> {
+        let mut window = Window::new()
+            .set_mode(Mode::Nomal)
+            .set_width(self.window_width as usize - 2);
+        // 線を描き始める一番上（予測変換ウィンドウの上限ライン）------
+        window = window.set_start_hight(self.window_height as usize - 1);
+        window.top_line()?;
+        // -----------------------------------------------------------
+
+        let put_data = format!("move {} to trash ? [Y/N]", self.targets_label());
+        // 予測変換たち v -> 予想されるファイル・ディレクトリの集合---------------------------------------------------
+        window.set_color(Color::Red).put(put_data)?;
+        Ok(())
+    }
+
+    fn draw_transfer(&mut self) -> Result<()> {
+        let transfer = match self.transfer.as_ref() {
+            Some(transfer) => transfer,
+            None => return Ok(()),
+        };
+        let (done_items, total_items) = transfer.items();
+        let (done_bytes, total_bytes) = transfer.bytes();
+        let percent = transfer.percent();
+        let title = if transfer.is_move() {
+            "[move]"
+        } else {
+            "[copy]"
+        };
+
+        let bar_width = self.window_width as usize - 16;
+        let filled = bar_width * percent / 100;
+        let bar = format!(
+            "[{}{}] {:>3}%",
+            "#".repeat(filled),
+            ".".repeat(bar_width - filled),
+            percent
+        );
+        let lines = vec![
+            transfer.current_name().to_string(),
+            bar,
+            format!(
+                "{}/{} items  {}/{} B  (Esc: cancel)",
+                done_items, total_items, done_bytes, total_bytes
+            ),
+        ];
+
+        let mut window = Window::new()
+            .set_mode(Mode::Nomal)
+            .set_width(self.window_width as usize - 2);
+        window = window
+            .set_start_hight(self.window_height as usize - lines.len())
+            .set_hight(lines.len());
+        window.set_title(title.to_string()).top_line()?;
+        for line in lines {
+            window.set_color(Color::Blue).put(line)?;
+        }
+        Ok(())
+    }
+
+    fn draw_conflict(&mut self) -> Result<()> {
+        let conflict = match self.transfer.as_ref().and_then(|t| t.conflict()) {
+            Some(conflict) => conflict.display().to_string(),
+            None => return Ok(()),
+        };
+        let lines = vec![
+            format!("\"{}\" already exists", conflict),
+            "[o]verwrite / [s]kip / [r]ename  (O/S/R: apply to all, Esc: cancel)".to_string(),
+        ];
 
-            queue!(std::io::stderr(), SetForegroundColor(Color::Blue),)?;
-            self.cursor.0 = self.window_width - 1;
+        let mut window = Window::new()
+            .set_mode(Mode::Nomal)
+            .set_width(self.window_width as usize - 2);
+        window = window
+            .set_start_hight(self.window_height as usize - lines.len())
+            .set_hight(lines.len());
+        window.set_title("[conflict]".to_string()).top_line()?;
+        for line in lines {
+            window.set_color(Color::Red).put(line)?;
+        }
+        Ok(())
+    }
 
-            queue!(
-                std::io::stderr(),
-                MoveToColumn((self.cursor.0 + self.start_w).try_into().unwrap()),
-                Print("│"),
-                MoveTo(
-                    self.start_w,
-                    (i as u16 + self.cursor.1 + self.start_h + 1)
-                        .try_into()
-                        .unwrap()
+    fn draw_rename_preview(&mut self) -> Result<()> {
+        let max_lines = self.window_height as usize - 6;
+        let mut lines: Vec<String> = self
+            .rename_plan
+            .iter()
+            .take(max_lines)
+            .map(|(src, dst)| {
+                format!(
+                    "{} -> {}",
+                    src.file_name().unwrap_or_default().to_string_lossy(),
+                    dst.file_name().unwrap_or_default().to_string_lossy()
                 )
-            )?;
-        }
-        if print_strings.len() > 0 {
-            self.cursor.1 += print_strings.len() as u16 - 1;
+            })
+            .collect();
+        if self.rename_plan.len() > max_lines {
+            lines.push(format!(
+                "... and {} more",
+                self.rename_plan.len() - max_lines
+            ));
         }
 
-        queue!(
-            std::io::stderr(),
-            MoveToRow(self.cursor.1 + self.start_h + 2),
-        )?;
+        let mut window = Window::new()
+            .set_mode(Mode::Nomal)
+            .set_width(self.window_width as usize - 2);
+        window = window
+            .set_start_hight(self.window_height as usize - lines.len())
+            .set_hight(lines.len());
+        window
+            .set_title(format!("[rename {} items ? [Y/N]]", self.rename_plan.len()))
+            .top_line()?;
+        for line in lines {
+            window.set_color(Color::Blue).put(line)?;
+        }
+        Ok(())
+    }
 
-        // 何も表示することがない場合の空欄
-        for i in if print_strings.len() > self.window_height as usize - 2 {
-            0..0
+    fn draw_trash(&mut self) -> Result<()> {
+        let max_lines = self.window_height as usize - 6;
+        let offset = if self.trash_focus >= max_lines {
+            self.trash_focus + 1 - max_lines
         } else {
-            self.cursor.1..self.window_height - 1
-        } {
-            queue!(std::io::stderr(), Print("│"))?;
-            for _ in 1..self.window_width - 1 {
-                queue!(std::io::stderr(), Print(" "))?;
-            }
-            queue!(
-                std::io::stderr(),
-                Print("│"),
-                MoveTo(
-                    self.start_w,
-                    (i as u16 + self.start_h + 1).try_into().unwrap()
-                )
-            )?;
+            0
+        };
+        let mut lines: Vec<(Color, String)> = self
+            .trash_entries
+            .iter()
+            .enumerate()
+            .skip(offset)
+            .take(max_lines)
+            .map(|(i, entry)| {
+                let line = format!(
+                    "{} {}  {}",
+                    if i == self.trash_focus { ">" } else { " " },
+                    entry.deleted_at,
+                    entry.original.display()
+                );
+                if i == self.trash_focus {
+                    (Color::Cyan, line)
+                } else {
+                    (Color::Blue, line)
+                }
+            })
+            .collect();
+        if lines.is_empty() {
+            lines.push((Color::Blue, "trash is empty".to_string()));
         }
+        let footer = if let Mode::TrashPurge = self.mode {
+            (Color::Red, "delete permanently ? [Y/N]".to_string())
+        } else if !self.message.is_empty() {
+            (Color::Yellow, self.message.clone())
+        } else {
+            (
+                Color::Blue,
+                "r: restore  x: delete permanently  q: close".to_string(),
+            )
+        };
+        lines.push(footer);
 
-        // 下の枠
-        queue!(std::io::stderr(), Print("└"))?;
-
-        for _ in 1..self.window_width - 1 {
-            queue!(std::io::stderr(), Print("─"))?;
+        let mut window = Window::new()
+            .set_mode(Mode::Nomal)
+            .set_width(self.window_width as usize - 2);
+        window = window
+            .set_start_hight(self.window_height as usize - lines.len())
+            .set_hight(lines.len());
+        window
+            .set_title(format!("[trash {}]", self.trash_entries.len()))
+            .top_line()?;
+        for (color, line) in lines {
+            window.set_color(color).put(line)?;
         }
-        queue!(std::io::stderr(), Print("┘"))?;
-        // -------------------------
         Ok(())
     }
 
-    pub fn ui(&mut self, print_strings: Vec<String>) -> Result<()> {
-        self.nomal_ui(print_strings)?;
+    // ジョブ -----------------------------------------------------------------------------------
 
-        match self.mode {
-            Mode::Nomal => {}
-            Mode::Edit => {}
-            Mode::Cd => {
-                let auto_correct = self.find_dir(
-                    self.input_buffer.to_owned(),
-                    self.in_dir_files.get(self.focus_page).unwrap().to_owned(),
-                );
-                self.draw_auto_correct(auto_correct, "[file open]")?;
+    // キー入力を待つ
+    // 実行中のジョブがある間は定期的に終わったか確かめ、画面を描き直す必要があればfalseを返す
+    fn wait_for_input(&mut self) -> Result<bool> {
+        if let Mode::Transfer = self.mode {
+            return Ok(true);
+        }
+        while self.jobs.is_running() {
+            if poll(Duration::from_millis(200))? {
+                return Ok(true);
             }
-            Mode::Command => {
-                self.draw_command_window("[command mode]")?;
+            if self.check_jobs() || matches!(self.mode, Mode::Jobs) {
+                return Ok(false);
             }
-            Mode::Addfile => {
-                let auto_correct = self.find_dir(
-                    self.input_buffer.to_owned(),
-                    self.in_dir_files.get(self.focus_page).unwrap().to_owned(),
-                );
-                self.draw_auto_correct(auto_correct, "[add new file]")?;
+        }
+        Ok(true)
+    }
+
+    // 終わったジョブを知らせる (他の入力の邪魔をしないよう通常のモードのときだけ)
+    // そのジョブを実行したディレクトリの中にいれば一覧を読み直す
+    fn check_jobs(&mut self) -> bool {
+        let mut changed = self.jobs.update();
+        if !matches!(self.mode, Mode::Nomal | Mode::Jobs) {
+            return changed;
+        }
+        let finished: Vec<(String, PathBuf)> = self
+            .jobs
+            .take_finished()
+            .iter()
+            .map(|job| {
+                (
+                    format!("[{}] {}: {}", job.id, job.status_label(), job.command),
+                    job.cwd.clone(),
+                )
+            })
+            .collect();
+        for (notice, cwd) in finished {
+            if Path::new(&self.pwd).starts_with(&cwd) {
+                self.refresh_dir_view();
             }
-            Mode::Delfile => {
-                self.draw_remove_file()?;
+            if let Mode::Nomal = self.mode {
+                self.message = notice;
             }
-            _ => {}
+            changed = true;
+        }
+        changed
+    }
+
+    // フォーカスを保ったまま読み直す
+    fn refresh_dir_view(&mut self) {
+        let focused = self.focused_name();
+        if self.get_in_dir().is_err() {
+            return;
         }
+        self.focus_page = self
+            .focus_page
+            .min(self.in_dir_files.len().saturating_sub(1));
+        match focused {
+            Some(name) => self.focus_name(&name),
+            None => self.focus_index = 0,
+        }
+        let len = self
+            .in_dir_files
+            .get(self.focus_page)
+            .map_or(0, |page| page.len());
+        self.focus_index = self.focus_index.min(len.saturating_sub(1));
+    }
 
+    fn jobs_key_read(&mut self) -> Result<()> {
+        if let Event::Key(KeyEvent { code, .. }) = read()? {
+            self.message.clear();
+            let len = self.jobs.list().len();
+            let selected = self.jobs.list().get(self.jobs_focus).map(|job| job.id);
+            match code {
+                KeyCode::Esc | KeyCode::Char('q') => {
+                    self.mode = Mode::Nomal;
+                }
+                KeyCode::Char('j') | KeyCode::Down => {
+                    if self.jobs_focus + 1 < len {
+                        self.jobs_focus += 1;
+                    }
+                }
+                KeyCode::Char('k') | KeyCode::Up => {
+                    self.jobs_focus = self.jobs_focus.saturating_sub(1);
+                }
+                KeyCode::Char('x') => {
+                    if let Some(id) = selected {
+                        if let Err(e) = self.jobs.kill(id) {
+                            self.message = e.to_string();
+                        }
+                    }
+                }
+                KeyCode::Char('d') => {
+                    if let Some(id) = selected {
+                        self.jobs.remove(id);
+                        let len = self.jobs.list().len();
+                        self.jobs_focus = self.jobs_focus.min(len.saturating_sub(1));
+                    }
+                }
+                KeyCode::Enter => {
+                    if let Some(job) = self.jobs.list().get(self.jobs_focus) {
+                        self.output =
+                            Some(Output::new(&job.command, job.status_label(), &job.output()));
+                        self.mode = Mode::Output;
+                    }
+                }
+                _ => {}
+            }
+        }
         Ok(())
     }
 
-    fn draw_remove_file(&mut self) -> Result<()> {
+    fn draw_jobs(&mut self) -> Result<()> {
+        let max_lines = self.window_height as usize - 6;
+        let offset = (self.jobs_focus + 1).saturating_sub(max_lines);
+        let width = self.window_width as usize - 5;
+        let mut lines: Vec<(Color, String)> = self
+            .jobs
+            .list()
+            .iter()
+            .enumerate()
+            .skip(offset)
+            .take(max_lines)
+            .map(|(row, job)| {
+                let elapsed = job.elapsed().as_secs();
+                let line = format!(
+                    "{} [{}] {:<10} {:>02}:{:>02}:{:>02}  {}",
+                    if row == self.jobs_focus { ">" } else { " " },
+                    job.id,
+                    job.status_label(),
+                    elapsed / 3600,
+                    elapsed / 60 % 60,
+                    elapsed % 60,
+                    job.command
+                );
+                let color = if row == self.jobs_focus {
+                    Color::Cyan
+                } else if job.is_running() {
+                    Color::Blue
+                } else {
+                    Color::DarkGrey
+                };
+                (color, truncate_to_width(&line, width))
+            })
+            .collect();
+        if lines.is_empty() {
+            lines.push((
+                Color::Blue,
+                "no jobs (run one with :!command &)".to_string(),
+            ));
+        }
+        if self.message.is_empty() {
+            lines.push((
+                Color::Blue,
+                "Enter: output  x: kill  d: remove  q: close".to_string(),
+            ));
+        } else {
+            lines.push((Color::Yellow, self.message.clone()));
+        }
+
+        let mut window = Window::new()
+            .set_mode(Mode::Nomal)
+            .set_width(self.window_width as usize - 2);
+        window = window
+            .set_start_hight(self.window_height as usize - lines.len())
+            .set_hight(lines.len());
+        window.set_title("[jobs]".to_string()).top_line()?;
+        for (color, line) in lines {
+            window.set_color(color).put(line)?;
+        }
+        Ok(())
+    }
+    // ジョブ -----------------------------------------------------------------------------------
+
+    fn draw_history(&mut self) -> Result<()> {
+        let max_lines = self.window_height as usize - 6;
+        let offset = if self.history_focus >= max_lines {
+            self.history_focus + 1 - max_lines
+        } else {
+            0
+        };
+        let position = self.journal.position();
+        let mut lines: Vec<(Color, String)> = self
+            .journal
+            .records()
+            .iter()
+            .enumerate()
+            .rev()
+            .enumerate()
+            .skip(offset)
+            .take(max_lines)
+            .map(|(row, (i, record))| {
+                let undone = i >= position;
+                let line = format!(
+                    "{} {} {}  {}",
+                    if row == self.history_focus { ">" } else { " " },
+                    if undone { "(undone)" } else { "        " },
+                    record.time,
+                    record.describe()
+                );
+                if row == self.history_focus {
+                    (Color::Cyan, line)
+                } else if undone {
+                    (Color::DarkGrey, line)
+                } else {
+                    (Color::Blue, line)
+                }
+            })
+            .collect();
+        if lines.is_empty() {
+            lines.push((Color::Blue, "no operations yet".to_string()));
+        }
+        if self.message.is_empty() {
+            lines.push((Color::Blue, "u: undo  Ctrl-r: redo  q: close".to_string()));
+        } else {
+            lines.push((Color::Yellow, self.message.clone()));
+        }
+
+        let mut window = Window::new()
+            .set_mode(Mode::Nomal)
+            .set_width(self.window_width as usize - 2);
+        window = window
+            .set_start_hight(self.window_height as usize - lines.len())
+            .set_hight(lines.len());
+        window.set_title("[history]".to_string()).top_line()?;
+        for (color, line) in lines {
+            window.set_color(color).put(line)?;
+        }
+        Ok(())
+    }
+
+    fn draw_message(&mut self) -> Result<()> {
         let mut window = Window::new()
             .set_mode(Mode::Nomal)
             .set_width(self.window_width as usize - 2);
-        // 線を描き始める一番上（予測変換ウィンドウの上限ライン）------
         window = window.set_start_hight(self.window_height as usize - 1);
         window.top_line()?;
-        // -----------------------------------------------------------
 
-        let put_data = format!(
-            "remove \"{}\" ? [Y/N]",
-            self.in_dir_files[self.focus_page][self.focus_index]
-        );
-        // 予測変換たち v -> 予想されるファイル・ディレクトリの集合---------------------------------------------------
-        window.set_color(Color::Red).put(put_data)?;
+        window.set_color(Color::Yellow).put(self.message.clone())?;
         Ok(())
     }
 
@@ -811,10 +2672,28 @@ impl App {
         Ok(())
     }
 
-    pub fn draw_auto_correct(&mut self, v: Vec<String>, title: &str) -> Result<()> {
+    pub fn draw_auto_correct(
+        &mut self,
+        v: Vec<String>,
+        selected: Option<usize>,
+        title: &str,
+    ) -> Result<()> {
         if v.is_empty() {
             self.draw_auto_correct_notfound()?;
         } else {
+            // 画面に収まる分だけ (選んでいるものが見えるように)
+            let max_lines = self.window_height as usize - 4;
+            let offset = match selected {
+                Some(index) if index >= max_lines => index + 1 - max_lines,
+                _ => 0,
+            };
+            let v: Vec<(usize, String)> = v
+                .into_iter()
+                .enumerate()
+                .skip(offset)
+                .take(max_lines)
+                .collect();
+
             let mut auto_correct_window = Window::new()
                 .set_mode(Mode::Nomal)
                 .set_width(self.window_width as usize - 2);
@@ -829,10 +2708,16 @@ impl App {
             // -----------------------------------------------------------
 
             // 予測変換たち v -> 予想されるファイル・ディレクトリの集合---------------------------------------------------
-            for (_, item) in v.iter().enumerate() {
-                auto_correct_window
-                    .set_color(Color::Blue)
-                    .put((*item).clone())?;
+            for (index, item) in v.iter() {
+                if Some(*index) == selected {
+                    auto_correct_window
+                        .set_color(Color::Cyan)
+                        .put(format!("> {}", item))?;
+                } else {
+                    auto_correct_window
+                        .set_color(Color::Blue)
+                        .put(format!("  {}", item))?;
+                }
             }
             // -----------------------------------------------------------------------------------------------------------
         }
@@ -843,64 +2728,34 @@ impl App {
             MoveTo(self.start_w, self.start_h + self.window_height)
         )?;
 
-        let mut text_line = TextLine::new(self.window_width as usize);
-
-        text_line
-            .create_text_box(Color::Blue, 4, 1)
-            .put("└[".to_string())?;
-
-        text_line
-            .create_text_box(Color::Blue, self.input_buffer.len(), 1)
-            .put(self.input_buffer.clone())?;
-
-        text_line.blank()?;
-
-        text_line
-            .create_text_box(Color::Blue, 4, 1)
-            .put("]┘".to_string())?;
-        Ok(())
+        self.draw_input_line()
     }
 
     fn command_key_read(&mut self) -> Result<()> {
-        if let Event::Key(KeyEvent { code, .. }) = read()? {
+        // エラーは次のキーを押すまで表示する
+        self.message.clear();
+        if let Some(code) = self.read_prompt_key()? {
+            self.complete_key(code);
             match code {
                 KeyCode::Esc => {
                     self.input_buffer.clear();
                     self.mode = Mode::Nomal;
                 }
-                KeyCode::Char(c) => {
-                    self.input_buffer.push(c);
-                }
-                KeyCode::Backspace => {
-                    self.input_buffer.pop();
-                }
                 KeyCode::Enter => {
-                    if self.input_buffer == 'q'.to_string() {
-                        self.exit_flag = true;
-                    } else {
-                        let mut args: Vec<String> = self
-                            .input_buffer
-                            .trim()
-                            .split(' ')
-                            .map(|s| s.to_string())
-                            .collect();
-
-                        // コマンドを実行
-                        let command = args[0].clone();
-                        args.remove(0);
-                        match Command::new(command).args(args).spawn() {
-                            Ok(mut child) => {
-                                child.wait().unwrap();
-                            }
-                            Err(e) => {
-                                self.input_buffer = e.to_string();
-                            }
+                    let line = self.input_buffer.as_str().to_string();
+                    self.command_history.push(&line);
+                    self.mode = Mode::Nomal;
+                    if line.trim().is_empty() {
+                        return Ok(());
+                    }
+                    // 失敗したら入力を残したままコマンドモードにとどまる
+                    match self.run_command(&line) {
+                        Ok(_) => self.input_buffer.clear(),
+                        Err(e) => {
+                            self.message = e.to_string();
+                            self.mode = Mode::Command;
                         }
-
-                        self.render_dir_view()?;
                     }
-                    self.input_buffer.clear();
-                    self.mode = Mode::Nomal;
                 }
 
                 _ => {}
@@ -910,6 +2765,201 @@ impl App {
         Ok(())
     }
 
+    fn command_path(&self, path: &str) -> PathBuf {
+        Path::new(&self.pwd).join(complete::expand(path))
+    }
+
+    fn command_paths(&self, paths: &[String]) -> Vec<String> {
+        paths
+            .iter()
+            .map(|path| self.command_path(path).display().to_string())
+            .collect()
+    }
+
+    // コマンドモードで入力された1行を実行する
+    fn run_command(&mut self, line: &str) -> Result<()> {
+        match command::parse(line)? {
+            Action::Cd(dir) => self.cd(dir),
+            Action::Mkdir(paths) => self.create_paths(&paths, true),
+            Action::Touch(paths) => self.create_paths(&paths, false),
+            Action::Rm(paths) => {
+                let targets = self.command_paths(&paths);
+                let result = self.trash_paths(targets);
+                self.render_dir_view()?;
+                result
+            }
+            Action::Mv(sources, destination) => {
+                let sources = self.command_paths(&sources);
+                let destination = self.command_path(&destination);
+                self.start_transfer(sources, destination, true)
+            }
+            Action::Cp(sources, destination) => {
+                let sources = self.command_paths(&sources);
+                let destination = self.command_path(&destination);
+                self.start_transfer(sources, destination, false)
+            }
+            Action::Chmod(mode, paths) => {
+                let targets = self.command_paths(&paths);
+                let result = self.chmod_paths(mode, targets);
+                self.render_dir_view()?;
+                result
+            }
+            Action::Sort(key, reverse) => {
+                self.sort_key = key;
+                self.sort_reverse = reverse;
+                self.render_dir_view()
+            }
+            Action::SetHidden(show_hidden) => {
+                self.show_hidden = show_hidden;
+                self.render_dir_view()
+            }
+            Action::SetCapture(capture_output) => {
+                self.config.capture_output = capture_output;
+                Ok(())
+            }
+            Action::Bookmark(name) => {
+                let pwd = PathBuf::from(&self.pwd);
+                let name = match name {
+                    Some(name) => name,
+                    None => pwd
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_else(|| "/".to_string()),
+                };
+                if name.contains('\t') {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("invalid bookmark name: \"{}\"", name),
+                    ));
+                }
+                self.bookmarks.add(&name, &pwd)?;
+                self.message = format!("bookmarked {} as {}", pwd.display(), name);
+                Ok(())
+            }
+            Action::DeleteBookmark(name) => {
+                if !self.bookmarks.remove(&name)? {
+                    return Err(Error::new(
+                        ErrorKind::NotFound,
+                        format!("no such bookmark: {}", name),
+                    ));
+                }
+                self.message = format!("deleted bookmark {}", name);
+                Ok(())
+            }
+            Action::Help => {
+                self.mode = Mode::Help;
+                Ok(())
+            }
+            Action::Quit => {
+                self.exit_flag = true;
+                Ok(())
+            }
+            Action::Shell(command, background) => {
+                let focused = self.focused_name().map(|name| self.absolute_path(&name));
+                let command = command::expand_placeholders(
+                    &command,
+                    focused.as_deref(),
+                    &self.targets(),
+                    &self.pwd,
+                )?;
+                if background {
+                    let id = self
+                        .jobs
+                        .spawn(&Self::shell(), &command, Path::new(&self.pwd))?;
+                    self.message = format!("[{}] started: {}", id, command.trim());
+                    Ok(())
+                } else {
+                    self.run_shell(&command)
+                }
+            }
+        }
+    }
+
+    // :mkdir / :touch (:touch は既にあるファイルなら更新日時だけ変える)
+    fn create_paths(&mut self, paths: &[String], is_dir: bool) -> Result<()> {
+        let mut operations = Vec::new();
+        let mut result = Ok(());
+        for path in paths {
+            let path = self.command_path(path);
+            if !is_dir && path.is_file() {
+                if let Err(e) = fs::File::options()
+                    .write(true)
+                    .open(&path)
+                    .and_then(|file| file.set_modified(SystemTime::now()))
+                {
+                    result = Err(e);
+                    break;
+                }
+                continue;
+            }
+            let template = if is_dir {
+                None
+            } else {
+                config::template_for(&path)
+            };
+            match fileops::create_entry(&path, is_dir, template.as_deref()) {
+                Ok(created) => operations.push(Operation::create(created)),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        self.journal.record(operations);
+        self.render_dir_view()?;
+        result
+    }
+
+    fn help_key_read(&mut self) -> Result<()> {
+        if let Event::Key(_) = read()? {
+            self.mode = Mode::Nomal;
+        }
+        Ok(())
+    }
+
+    fn draw_help(&mut self) -> Result<()> {
+        let usage_width = command::COMMANDS
+            .iter()
+            .map(|(_, usage, _)| usage.len())
+            .max()
+            .unwrap_or(0);
+        let mut lines: Vec<String> = command::COMMANDS
+            .iter()
+            .map(|(_, usage, description)| {
+                format!(":{:width$}  {}", usage, description, width = usage_width)
+            })
+            .collect();
+
+        let mut window = Window::new()
+            .set_mode(Mode::Nomal)
+            .set_width(self.window_width as usize - 2);
+        window = window
+            .set_start_hight(self.window_height as usize - lines.len())
+            .set_hight(lines.len());
+        window.set_title("[help]".to_string()).top_line()?;
+        for line in lines {
+            window.set_color(Color::Blue).put(line)?;
+        }
+        Ok(())
+    }
+
+    // コマンドのエラー (入力欄はそのまま)
+    fn draw_command_error(&mut self) -> Result<()> {
+        let mut window = Window::new()
+            .set_mode(Mode::Nomal)
+            .set_width(self.window_width as usize - 2);
+        window = window.set_start_hight(self.window_height as usize - 1);
+        window.set_title("[command mode]".to_string()).top_line()?;
+        window.set_color(Color::Red).put(self.message.clone())?;
+
+        queue!(
+            std::io::stderr(),
+            SetBackgroundColor(GRUVBOX_BACKGROUND),
+            MoveTo(self.start_w, self.start_h + self.window_height)
+        )?;
+        self.draw_input_line()
+    }
+
     pub fn draw_command_window(&mut self, title: &str) -> Result<()> {
         let mut command_window = Window::new()
             .set_mode(Mode::Nomal)
@@ -927,45 +2977,106 @@ impl App {
             MoveTo(self.start_w, self.start_h + self.window_height)
         )?;
 
-        let mut text_line = TextLine::new(self.window_width as usize);
+        self.draw_input_line()
+    }
+
+    // 入力欄 (カーソル位置の文字を反転して表示)
+    // 入りきらない場合はカーソルが見える位置まで横にずらす
+    fn draw_input_line(&mut self) -> Result<()> {
+        let mut text_line = TextLine::new(self.window_width as usize - 2);
+
+        // 履歴の検索中は見つけたものを表示する
+        let (label, before, after) = match &self.history_search {
+            Some((query, found)) => {
+                let entry = found
+                    .and_then(|index| self.history().and_then(|history| history.get(index)))
+                    .unwrap_or("");
+                let start = entry.find(query.as_str()).unwrap_or(0);
+                let (before, after) = entry.split_at(start);
+                (format!("(search)`{}': ", query), before, after)
+            }
+            None => {
+                let (before, after) = self.input_buffer.split_at_cursor();
+                (String::new(), before, after)
+            }
+        };
+        let room = (self.window_width as usize).saturating_sub(5 + display_width(&label));
+
+        let mut after_chars = after.chars();
+        let cursor_char = after_chars.next().unwrap_or(' ');
+        let mut after = after_chars.as_str().to_string();
+        let mut before = before.to_string();
+
+        while display_width(&before) + char_width(cursor_char) > room && !before.is_empty() {
+            before.remove(0);
+        }
+        while display_width(&before) + char_width(cursor_char) + display_width(&after) > room {
+            if after.pop().is_none() {
+                break;
+            }
+        }
 
         text_line
-            .create_text_box(Color::Blue, 4, 1)
+            .create_text_box(Color::Blue, 2, 1)
             .put("└[".to_string())?;
-
         text_line
-            .create_text_box(Color::Blue, self.input_buffer.len(), 1)
-            .put(self.input_buffer.clone())?;
+            .create_text_box(Color::Yellow, display_width(&label), 1)
+            .put(label)?;
+        text_line
+            .create_text_box(Color::Blue, display_width(&before), 1)
+            .put(before)?;
+        queue!(std::io::stderr(), SetAttribute(Attribute::Reverse))?;
+        text_line
+            .create_text_box(Color::Blue, char_width(cursor_char), 1)
+            .put(cursor_char.to_string())?;
+        queue!(std::io::stderr(), SetAttribute(Attribute::NoReverse))?;
+        text_line
+            .create_text_box(Color::Blue, display_width(&after), 1)
+            .put(after)?;
 
         text_line.blank()?;
 
         text_line
-            .create_text_box(Color::Blue, 4, 1)
+            .create_text_box(Color::Blue, 2, 1)
             .put("]┘".to_string())?;
         Ok(())
     }
 
     pub fn main(&mut self) -> Result<()> {
         self.get_in_dir()?;
-        execute!(std::io::stderr(), Hide, EnterAlternateScreen)?;
+        execute!(
+            std::io::stderr(),
+            Hide,
+            EnterAlternateScreen,
+            EnableBracketedPaste
+        )?;
 
         loop {
             // ui
             let _ = self.ui(self.in_dir_files[self.focus_page].clone());
             // Key Read
-            let _ = self.key_read(
-                if self.in_dir_files.len() > self.window_height as usize - 1 {
-                    self.window_height as usize - 1
-                } else {
-                    self.in_dir_files[self.focus_page].len()
-                },
-            );
+            if self.wait_for_input()? {
+                let _ = self.key_read(
+                    if self.in_dir_files.len() > self.window_height as usize - 1 {
+                        self.window_height as usize - 1
+                    } else {
+                        self.in_dir_files[self.focus_page].len()
+                    },
+                );
+                self.check_jobs();
+            }
 
             if self.exit_flag {
                 break;
             }
         }
-        execute!(std::io::stderr(), Show, LeaveAlternateScreen)?;
+        self.jobs.kill_all();
+        execute!(
+            std::io::stderr(),
+            DisableBracketedPaste,
+            Show,
+            LeaveAlternateScreen
+        )?;
         return Ok(());
     }
 }
@@ -0,0 +1,129 @@
+use std::process::ExitStatus;
+
+// これより長い出力は枠ではなくページャで読む
+pub const PAGER_THRESHOLD: usize = 10000;
+
+// :! で実行したコマンドの出力を表示する枠
+pub struct Output {
+    command: String,
+    status: String,
+    lines: Vec<String>,
+    scroll: usize,
+    query: String,
+}
+
+impl Output {
+    pub fn new(command: &str, status: String, text: &str) -> Self {
+        Self {
+            command: command.trim().to_string(),
+            status,
+            lines: text.lines().map(sanitize).collect(),
+            scroll: 0,
+            query: String::new(),
+        }
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    pub fn text(&self) -> String {
+        let mut text = self.lines.join("\n");
+        text.push('\n');
+        text
+    }
+
+    pub fn scroll(&self) -> usize {
+        self.scroll
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn title(&self, max_width: usize) -> String {
+        let mut command: String = self.command.chars().take(max_width).collect();
+        if self.command.chars().count() > max_width {
+            command.push_str("...");
+        }
+        format!("[{}]-[{}]", command, self.status)
+    }
+
+    // heightは一度に表示できる行数
+    pub fn scroll_by(&mut self, delta: isize, height: usize) {
+        let max = self.lines.len().saturating_sub(height);
+        self.scroll = self.scroll.saturating_add_signed(delta).min(max);
+    }
+
+    pub fn scroll_to_end(&mut self, height: usize) {
+        self.scroll = self.lines.len().saturating_sub(height);
+    }
+
+    pub fn set_query(&mut self, query: &str) {
+        self.query = query.to_string();
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        !self.query.is_empty() && line.contains(&self.query)
+    }
+
+    // 今の位置の次 (前) にある一致する行を一番上に表示する (端まで行ったら反対側から)
+    pub fn search(&mut self, forward: bool) -> bool {
+        let len = self.lines.len();
+        let found = (1..=len)
+            .map(|offset| {
+                if forward {
+                    (self.scroll + offset) % len
+                } else {
+                    (self.scroll + len - offset) % len
+                }
+            })
+            .find(|index| self.is_match(&self.lines[*index]));
+        if let Some(index) = found {
+            self.scroll = index;
+        }
+        found.is_some()
+    }
+}
+
+// "exit 0" / "signal: 15 (SIGTERM)" など
+pub fn status_label(status: &ExitStatus) -> String {
+    match status.code() {
+        Some(code) => format!("exit {}", code),
+        None => status.to_string(),
+    }
+}
+
+// エスケープシーケンスと制御文字を取り除き、タブは空白にする
+// \r で上書きされる行 (進捗表示など) は最後の部分だけ残す
+fn sanitize(line: &str) -> String {
+    let line = line
+        .rsplit('\r')
+        .find(|part| !part.is_empty())
+        .unwrap_or("");
+    let mut sanitized = String::new();
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => {
+                if chars.peek() == Some(&'[') {
+                    chars.next();
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                } else {
+                    chars.next();
+                }
+            }
+            '\t' => {
+                let spaces = 8 - sanitized.chars().count() % 8;
+                sanitized.push_str(&" ".repeat(spaces));
+            }
+            c if c.is_control() => {}
+            c => sanitized.push(c),
+        }
+    }
+    sanitized
+}
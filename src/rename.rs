@@ -0,0 +1,99 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+// 一括リネーム
+// エディタで編集した名前の一覧から (元, 新) の組を作り、衝突がないか確かめてから適用する
+
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains('/')
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidInput, message)
+}
+
+// 名前の変わらないものは除く
+pub fn plan(sources: &[PathBuf], new_names: &[String]) -> Result<Vec<(PathBuf, PathBuf)>> {
+    if sources.len() != new_names.len() {
+        return Err(invalid(format!(
+            "expected {} lines, got {}",
+            sources.len(),
+            new_names.len()
+        )));
+    }
+
+    let mut pairs = Vec::new();
+    for (src, name) in sources.iter().zip(new_names) {
+        let name = name.trim();
+        if !is_valid_name(name) {
+            return Err(invalid(format!("invalid name: \"{}\"", name)));
+        }
+        let dst = src.with_file_name(name);
+        if dst != *src {
+            pairs.push((src.clone(), dst));
+        }
+    }
+
+    // 同じ名前への変更 / 変更されずに残るものとの衝突
+    let mut destinations = HashSet::new();
+    let renamed: HashSet<&PathBuf> = pairs.iter().map(|(src, _)| src).collect();
+    for (_, dst) in &pairs {
+        if !destinations.insert(dst.clone()) {
+            return Err(invalid(format!(
+                "\"{}\" is used more than once",
+                dst.display()
+            )));
+        }
+        if fs::symlink_metadata(dst).is_ok() && !renamed.contains(dst) {
+            return Err(invalid(format!("\"{}\" already exists", dst.display())));
+        }
+    }
+    Ok(pairs)
+}
+
+// a -> b, b -> a のような循環があっても壊れないよう、
+// 変更先が他の変更元と重なる場合は一度仮の名前を経由する
+pub fn apply(pairs: &[(PathBuf, PathBuf)]) -> Result<()> {
+    let sources: HashSet<&PathBuf> = pairs.iter().map(|(src, _)| src).collect();
+    if !pairs.iter().any(|(_, dst)| sources.contains(dst)) {
+        for (src, dst) in pairs {
+            fs::rename(src, dst)?;
+        }
+        return Ok(());
+    }
+
+    let mut temporaries = Vec::new();
+    for (i, (src, dst)) in pairs.iter().enumerate() {
+        let temporary = temporary_path(src, i);
+        if let Err(e) = fs::rename(src, &temporary) {
+            // 仮の名前にしたものを元に戻す
+            for (src, temporary, _) in temporaries.iter().rev() {
+                let _ = fs::rename(temporary, src);
+            }
+            return Err(e);
+        }
+        temporaries.push((src.clone(), temporary, dst.clone()));
+    }
+    for (_, temporary, dst) in &temporaries {
+        fs::rename(temporary, dst)?;
+    }
+    Ok(())
+}
+
+fn temporary_path(src: &Path, index: usize) -> PathBuf {
+    let mut counter = 0;
+    loop {
+        let candidate = src.with_file_name(format!(
+            ".lsl-rename-{}-{}-{}",
+            std::process::id(),
+            index,
+            counter
+        ));
+        if fs::symlink_metadata(&candidate).is_err() {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
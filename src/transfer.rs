@@ -0,0 +1,302 @@
+use std::collections::VecDeque;
+use std::fs::{self, File, FileTimes};
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::path::{Path, PathBuf};
+
+use crate::fileops;
+
+// 一度に書き込む量 (この単位でキャンセルを受け付ける)
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resolution {
+    Overwrite,
+    Skip,
+    Rename,
+}
+
+enum Job {
+    MakeDir(PathBuf),
+    CopyFile {
+        src: PathBuf,
+        dst: PathBuf,
+        opened: Option<(File, File)>,
+    },
+    Symlink(PathBuf, PathBuf),
+    // 中身のコピーが終わったディレクトリに元の属性をつける
+    FinishDir(PathBuf, PathBuf),
+    // renameに失敗したらコピー+削除に切り替える
+    Rename(PathBuf, PathBuf),
+    Remove(PathBuf),
+}
+
+// コピー・移動の進行状況
+// 1回のstepでは1チャンク分だけ進めるので、呼び出し側はその合間にキー入力を見られる
+pub struct Transfer {
+    is_move: bool,
+    pending: VecDeque<(PathBuf, PathBuf)>,
+    jobs: VecDeque<Job>,
+    conflict: Option<(PathBuf, PathBuf)>,
+    apply_to_all: Option<Resolution>,
+    current_name: String,
+    total_items: usize,
+    done_items: usize,
+    total_bytes: u64,
+    done_bytes: u64,
+    current: Option<(PathBuf, PathBuf)>,
+    completed: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Transfer {
+    // sourcesをdestination(ディレクトリ)の中へ
+    pub fn new(sources: Vec<PathBuf>, destination: &Path, is_move: bool) -> Self {
+        let pending: VecDeque<(PathBuf, PathBuf)> = sources
+            .into_iter()
+            .map(|src| {
+                let dst = fileops::resolve_destination(&src, destination);
+                (src, dst)
+            })
+            .collect();
+        Self {
+            is_move,
+            total_items: pending.len(),
+            pending,
+            jobs: VecDeque::new(),
+            conflict: None,
+            apply_to_all: None,
+            current_name: String::new(),
+            done_items: 0,
+            total_bytes: 0,
+            done_bytes: 0,
+            current: None,
+            completed: Vec::new(),
+        }
+    }
+
+    pub fn is_move(&self) -> bool {
+        self.is_move
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.conflict.is_none() && self.pending.is_empty() && self.jobs.is_empty()
+    }
+
+    // 衝突していてユーザの判断待ちのもの
+    pub fn conflict(&self) -> Option<&Path> {
+        self.conflict.as_ref().map(|(_, dst)| dst.as_path())
+    }
+
+    pub fn resolve(&mut self, resolution: Resolution, apply_to_all: bool) -> Result<()> {
+        if apply_to_all {
+            self.apply_to_all = Some(resolution);
+        }
+        match self.conflict.take() {
+            Some((src, dst)) => self.plan_resolved(src, dst, resolution),
+            None => Ok(()),
+        }
+    }
+
+    pub fn current_name(&self) -> &str {
+        &self.current_name
+    }
+
+    pub fn items(&self) -> (usize, usize) {
+        (self.done_items, self.total_items)
+    }
+
+    // 最後まで終わったもの (元, 先)
+    pub fn completed(&self) -> &[(PathBuf, PathBuf)] {
+        &self.completed
+    }
+
+    pub fn bytes(&self) -> (u64, u64) {
+        (self.done_bytes, self.total_bytes)
+    }
+
+    // 0 ~ 100
+    pub fn percent(&self) -> usize {
+        if self.total_bytes > 0 {
+            (self.done_bytes * 100 / self.total_bytes) as usize
+        } else if self.total_items > 0 {
+            self.done_items * 100 / self.total_items
+        } else {
+            100
+        }
+    }
+
+    // 途中まで書いたファイルを消して中断
+    pub fn cancel(&mut self) {
+        for job in self.jobs.drain(..) {
+            if let Job::CopyFile {
+                dst,
+                opened: Some(_),
+                ..
+            } = job
+            {
+                let _ = fs::remove_file(dst);
+            }
+        }
+        self.pending.clear();
+        self.conflict = None;
+    }
+
+    pub fn step(&mut self) -> Result<()> {
+        if self.conflict.is_some() {
+            return Ok(());
+        }
+        if self.jobs.is_empty() {
+            if let Some((src, dst)) = self.pending.pop_front() {
+                self.plan(src, dst)?;
+            }
+            return Ok(());
+        }
+
+        let job = self.jobs.pop_front().unwrap();
+        match job {
+            Job::MakeDir(dst) => fs::create_dir_all(dst)?,
+            Job::CopyFile { src, dst, opened } => {
+                let (mut reader, mut writer) = match opened {
+                    Some(files) => files,
+                    None => {
+                        self.current_name = file_name(&src);
+                        (File::open(&src)?, File::create(&dst)?)
+                    }
+                };
+                let mut buffer = vec![0; CHUNK_SIZE];
+                let read_size = reader.read(&mut buffer)?;
+                if read_size == 0 {
+                    drop(writer);
+                    copy_attributes(&src, &dst)?;
+                } else {
+                    writer.write_all(&buffer[..read_size])?;
+                    self.done_bytes += read_size as u64;
+                    self.jobs.push_front(Job::CopyFile {
+                        src,
+                        dst,
+                        opened: Some((reader, writer)),
+                    });
+                }
+            }
+            Job::Symlink(target, dst) => std::os::unix::fs::symlink(target, dst)?,
+            Job::FinishDir(src, dst) => copy_attributes(&src, &dst)?,
+            Job::Rename(src, dst) => {
+                self.current_name = file_name(&src);
+                if fs::rename(&src, &dst).is_err() {
+                    // 別デバイス間 -> コピーしてから元を消す
+                    self.jobs.push_front(Job::Remove(src.clone()));
+                    let mut copy_jobs = VecDeque::new();
+                    self.plan_copy(&src, &dst, &mut copy_jobs)?;
+                    while let Some(job) = copy_jobs.pop_back() {
+                        self.jobs.push_front(job);
+                    }
+                }
+            }
+            Job::Remove(path) => fileops::remove_entry(&path)?,
+        }
+        if self.jobs.is_empty() {
+            self.done_items += 1;
+            if let Some(current) = self.current.take() {
+                self.completed.push(current);
+            }
+        }
+        Ok(())
+    }
+
+    fn plan(&mut self, src: PathBuf, dst: PathBuf) -> Result<()> {
+        if dst.starts_with(&src) && dst != src {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("cannot copy {} into itself", src.display()),
+            ));
+        }
+
+        if dst == src {
+            // 同じ場所への貼り付け -> コピーなら別名で複製、移動なら何もしない
+            if self.is_move {
+                self.done_items += 1;
+                Ok(())
+            } else {
+                self.plan_resolved(src, dst, Resolution::Rename)
+            }
+        } else if fs::symlink_metadata(&dst).is_ok() {
+            match self.apply_to_all {
+                Some(resolution) => self.plan_resolved(src, dst, resolution),
+                None => {
+                    self.conflict = Some((src, dst));
+                    Ok(())
+                }
+            }
+        } else {
+            self.plan_resolved(src, dst, Resolution::Overwrite)
+        }
+    }
+
+    fn plan_resolved(&mut self, src: PathBuf, dst: PathBuf, resolution: Resolution) -> Result<()> {
+        let dst = match resolution {
+            Resolution::Skip => {
+                self.done_items += 1;
+                return Ok(());
+            }
+            Resolution::Rename => fileops::unique_path(&dst),
+            Resolution::Overwrite => {
+                if dst == src {
+                    self.done_items += 1;
+                    return Ok(());
+                }
+                if fs::symlink_metadata(&dst).is_ok() {
+                    self.jobs.push_back(Job::Remove(dst.clone()));
+                }
+                dst
+            }
+        };
+
+        self.current = Some((src.clone(), dst.clone()));
+        if self.is_move {
+            self.jobs.push_back(Job::Rename(src, dst));
+        } else {
+            let mut jobs = VecDeque::new();
+            self.plan_copy(&src, &dst, &mut jobs)?;
+            self.jobs.append(&mut jobs);
+        }
+        Ok(())
+    }
+
+    fn plan_copy(&mut self, src: &Path, dst: &Path, jobs: &mut VecDeque<Job>) -> Result<()> {
+        let metadata = fs::symlink_metadata(src)?;
+        if metadata.is_dir() {
+            jobs.push_back(Job::MakeDir(dst.to_path_buf()));
+            for entry in fs::read_dir(src)? {
+                let entry = entry?;
+                self.plan_copy(&entry.path(), &dst.join(entry.file_name()), jobs)?;
+            }
+            jobs.push_back(Job::FinishDir(src.to_path_buf(), dst.to_path_buf()));
+        } else if metadata.file_type().is_symlink() {
+            jobs.push_back(Job::Symlink(fs::read_link(src)?, dst.to_path_buf()));
+        } else {
+            self.total_bytes += metadata.len();
+            jobs.push_back(Job::CopyFile {
+                src: src.to_path_buf(),
+                dst: dst.to_path_buf(),
+                opened: None,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+// パーミッションと更新・アクセス日時を引き継ぐ
+fn copy_attributes(src: &Path, dst: &Path) -> Result<()> {
+    let metadata = fs::metadata(src)?;
+    let times = FileTimes::new()
+        .set_accessed(metadata.accessed()?)
+        .set_modified(metadata.modified()?);
+    File::open(dst)?.set_times(times)?;
+    fs::set_permissions(dst, metadata.permissions())
+}
@@ -0,0 +1,191 @@
+use chrono::Local;
+use std::fs::{self, OpenOptions};
+use std::io::{Error, ErrorKind, Result, Write};
+use std::path::{Path, PathBuf};
+
+use crate::config;
+use crate::fileops;
+
+// freedesktop.org Trash specification
+//   $XDG_DATA_HOME/Trash/files/<name>           -> 捨てたもの
+//   $XDG_DATA_HOME/Trash/info/<name>.trashinfo  -> 元の場所と捨てた日時
+
+#[derive(Clone, Debug)]
+pub struct TrashEntry {
+    pub name: String,
+    pub original: PathBuf,
+    pub deleted_at: String,
+}
+
+fn trash_dir() -> PathBuf {
+    config::data_home().join("Trash")
+}
+
+fn files_dir() -> PathBuf {
+    trash_dir().join("files")
+}
+
+fn info_dir() -> PathBuf {
+    trash_dir().join("info")
+}
+
+fn info_path(name: &str) -> PathBuf {
+    info_dir().join(format!("{}.trashinfo", name))
+}
+
+// pathをゴミ箱へ移す
+pub fn trash(path: &Path) -> Result<TrashEntry> {
+    let original = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(path)
+    };
+    fs::create_dir_all(files_dir())?;
+    fs::create_dir_all(info_dir())?;
+
+    let base = original
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let deleted_at = Local::now().format("%Y-%m-%dT%H:%M:%S").to_string();
+
+    // .trashinfoをcreate_newで作ることで名前を予約する
+    let mut counter = 1;
+    let (name, mut info_file) = loop {
+        let name = if counter == 1 {
+            base.clone()
+        } else {
+            format!("{}.{}", base, counter)
+        };
+        counter += 1;
+        if fs::symlink_metadata(files_dir().join(&name)).is_ok() {
+            continue;
+        }
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(info_path(&name))
+        {
+            Ok(file) => break (name, file),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    };
+
+    let result = info_file
+        .write_all(
+            format!(
+                "[Trash Info]\nPath={}\nDeletionDate={}\n",
+                encode_path(&original),
+                deleted_at
+            )
+            .as_bytes(),
+        )
+        .and_then(|_| fileops::move_entry(&original, &files_dir().join(&name)));
+    if let Err(e) = result {
+        let _ = fs::remove_file(info_path(&name));
+        return Err(e);
+    }
+
+    Ok(TrashEntry {
+        name,
+        original,
+        deleted_at,
+    })
+}
+
+// 新しく捨てたものから順に
+pub fn list() -> Vec<TrashEntry> {
+    let mut entries: Vec<TrashEntry> = match fs::read_dir(info_dir()) {
+        Ok(read_dir) => read_dir
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                let name = file_name.strip_suffix(".trashinfo")?.to_string();
+                parse_info(name, &fs::read_to_string(entry.path()).ok()?)
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    entries.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at));
+    entries
+}
+
+pub fn find(name: &str) -> Option<TrashEntry> {
+    parse_info(name.to_string(), &fs::read_to_string(info_path(name)).ok()?)
+}
+
+fn parse_info(name: String, text: &str) -> Option<TrashEntry> {
+    let mut original = None;
+    let mut deleted_at = String::new();
+    for line in text.lines() {
+        if let Some(path) = line.strip_prefix("Path=") {
+            original = Some(PathBuf::from(decode_path(path)));
+        } else if let Some(date) = line.strip_prefix("DeletionDate=") {
+            deleted_at = date.to_string();
+        }
+    }
+    Some(TrashEntry {
+        name,
+        original: original?,
+        deleted_at,
+    })
+}
+
+// 元の場所へ戻す (同じ名前のものがあれば戻さない)
+pub fn restore(entry: &TrashEntry) -> Result<()> {
+    if fs::symlink_metadata(&entry.original).is_ok() {
+        return Err(Error::new(
+            ErrorKind::AlreadyExists,
+            format!("\"{}\" already exists", entry.original.display()),
+        ));
+    }
+    if let Some(parent) = entry.original.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fileops::move_entry(&files_dir().join(&entry.name), &entry.original)?;
+    fs::remove_file(info_path(&entry.name))
+}
+
+// ゴミ箱からも完全に消す
+pub fn purge(entry: &TrashEntry) -> Result<()> {
+    let path = files_dir().join(&entry.name);
+    if fs::symlink_metadata(&path).is_ok() {
+        fileops::remove_entry(&path)?;
+    }
+    fs::remove_file(info_path(&entry.name))
+}
+
+// Path= はURLと同じようにパーセントエンコードする
+fn encode_path(path: &Path) -> String {
+    let mut encoded = String::new();
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn decode_path(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
@@ -72,11 +72,7 @@ impl TextBox {
                     Print(data.clone()),
                 )?;
 
-                if self.width == data.len() {
-                    return Ok(());
-                }
-
-                for _ in 0..self.width - data.len() {
+                for _ in display_width(&data)..self.width {
                     queue!(std::io::stderr(), Print(" "))?;
                 }
             }
@@ -92,3 +88,43 @@ impl TextBox {
         self.width = new_width
     }
 }
+
+// 端末上での表示幅 (全角文字は2, 結合文字は0)
+pub fn display_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+// widthに収まるところまで
+pub fn truncate_to_width(text: &str, width: usize) -> String {
+    let mut truncated = String::new();
+    let mut now_width = 0;
+    for c in text.chars() {
+        now_width += char_width(c);
+        if now_width > width {
+            break;
+        }
+        truncated.push(c);
+    }
+    truncated
+}
+
+pub fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x0300..=0x036F | 0x200B..=0x200F | 0xFE00..=0xFE0F => 0,
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
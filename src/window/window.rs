@@ -1,3 +1,4 @@
+use crate::window::textbox::display_width;
 use crate::Color;
 use crate::TextLine;
 use crossterm::cursor::MoveTo;
@@ -13,7 +14,24 @@ pub enum Mode {
     Edit,
     Cd,
     Addfile,
+    Adddir,
     Delfile,
+    Glob,
+    Copy,
+    Move,
+    Chmod,
+    Transfer,
+    Conflict,
+    Rename,
+    BulkRename,
+    Purge,
+    Trash,
+    TrashPurge,
+    History,
+    Help,
+    Output,
+    OutputSearch,
+    Jobs,
 }
 
 #[derive(Debug, Clone)]
@@ -105,7 +123,7 @@ impl Window {
     pub fn put(&mut self, data: String) -> Result<()> {
         let mut put_line = TextLine::new(self.window_width as usize - 3);
         put_line
-            .create_text_box(self.now_color, data.len(), 1)
+            .create_text_box(self.now_color, display_width(&data), 1)
             .put(data)?;
         put_line.blank()?;
         queue!(